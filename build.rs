@@ -0,0 +1,36 @@
+use glsl_to_spirv::ShaderType;
+
+use std::{env, fs, io::Read, path::Path};
+
+fn main() {
+    println!("cargo:rerun-if-changed=shaders/vs.fx");
+    println!("cargo:rerun-if-changed=shaders/ps.fx");
+
+    // The drawer compiles the sources itself, so there is nothing to embed.
+    if env::var_os("CARGO_FEATURE_GLSL_RUNTIME").is_some() {
+        return;
+    }
+
+    let out = env::var("OUT_DIR").unwrap();
+    let out = Path::new(&out);
+
+    let vs = fs::read_to_string("shaders/vs.fx").unwrap();
+    let ps = fs::read_to_string("shaders/ps.fx").unwrap();
+
+    compile(&vs, ShaderType::Vertex, &out.join("vs.spv"));
+    compile(&ps, ShaderType::Fragment, &out.join("ps.spv"));
+    compile(&with_define(&ps, "SRGB"), ShaderType::Fragment, &out.join("ps_srgb.spv"));
+    compile(&with_define(&ps, "PICKING"), ShaderType::Fragment, &out.join("ps_pick.spv"));
+    compile(&with_define(&with_define(&ps, "SRGB"), "PICKING"), ShaderType::Fragment, &out.join("ps_srgb_pick.spv"));
+}
+
+fn with_define(code: &str, define: &str) -> String {
+    code.replacen("#version 450", &format!("#version 450\n#define {}", define), 1)
+}
+
+fn compile(code: &str, ty: ShaderType, path: &Path) {
+    let mut output = glsl_to_spirv::compile(code, ty).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    let mut spv = Vec::new();
+    output.read_to_end(&mut spv).unwrap();
+    fs::write(path, spv).unwrap();
+}
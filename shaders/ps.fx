@@ -1,14 +1,48 @@
 #version 450
 precision mediump float;
 
+layout(set = 0, binding = 0) uniform Locals {
+  mat4 ProjMtx;
+  vec4 Tint;
+  float Gamma;
+  float YScale;
+};
+
 layout(set = 1, binding = 0) uniform texture2D Texture;
 layout(set = 1, binding = 1) uniform sampler Sampler;
+layout(set = 1, binding = 2) uniform TextureLocals {
+  uint AlphaOnly;
+};
 
 layout(location = 0) in vec2 Frag_UV;
 layout(location = 1) in vec4 Frag_Color;
 
 layout(location = 0) out vec4 Target0;
 
+#ifdef PICKING
+layout(location = 2) flat in uint Frag_PickId;
+layout(location = 1) out uint Target1;
+#endif
+
+#ifdef SRGB
+vec3 to_linear(vec3 c) {
+  return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+#endif
+
 void main(){
-  Target0 = Frag_Color * texture(sampler2D(Texture, Sampler), Frag_UV.st);
+#ifdef SRGB
+  vec4 color = vec4(to_linear(Frag_Color.rgb), Frag_Color.a);
+#else
+  vec4 color = Frag_Color;
+#endif
+  vec4 tex = texture(sampler2D(Texture, Sampler), Frag_UV.st);
+  if (AlphaOnly != 0) {
+    tex = vec4(1.0, 1.0, 1.0, tex.r);
+  }
+  Target0 = color * tex * Tint;
+  Target0.rgb = pow(Target0.rgb, vec3(1.0 / Gamma));
+#ifdef PICKING
+  Target1 = Frag_PickId;
+#endif
 }
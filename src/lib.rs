@@ -1,58 +1,373 @@
 #![cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))] // TODO later
-use nuklear::{Buffer as NkBuffer, Context, ConvertConfig, DrawVertexLayoutAttribute, DrawVertexLayoutElements, DrawVertexLayoutFormat, Handle, Size, Vec2};
+#[macro_use]
+extern crate log;
+
+use nuklear::{Buffer as NkBuffer, Context, ConvertConfig, DrawCommand, DrawVertexLayoutAttribute, DrawVertexLayoutElements, DrawVertexLayoutFormat, Handle, Size, Vec2};
 
 use std::{
-    io::prelude::*,
-    mem::{forget, size_of, size_of_val},
+    error::Error,
+    fmt,
+    future::Future,
+    mem::size_of,
+    ops::Range,
+    ptr::null,
     slice::from_raw_parts,
-    str::from_utf8,
+    task::{Poll, RawWaker, RawWakerVTable, Waker},
 };
 
 use wgpu::*;
 
+#[cfg(feature = "winit-integration")]
+pub mod winit_integration;
+
+// Compiled from shaders/*.fx by build.rs.
+#[cfg(not(feature = "glsl-runtime"))]
+const VS_SPIRV: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/vs.spv"));
+#[cfg(not(feature = "glsl-runtime"))]
+const PS_SPIRV: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ps.spv"));
+#[cfg(not(feature = "glsl-runtime"))]
+const PS_SRGB_SPIRV: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ps_srgb.spv"));
+#[cfg(not(feature = "glsl-runtime"))]
+const PS_PICK_SPIRV: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ps_pick.spv"));
+#[cfg(not(feature = "glsl-runtime"))]
+const PS_SRGB_PICK_SPIRV: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ps_srgb_pick.spv"));
+
+// Compiled by `Drawer` itself with the `glsl-runtime` feature.
+#[cfg(feature = "glsl-runtime")]
+const VS_GLSL: &str = include_str!("../shaders/vs.fx");
+#[cfg(feature = "glsl-runtime")]
+const PS_GLSL: &str = include_str!("../shaders/ps.fx");
+
+/// Format of the textures uploaded through `add_texture`.
 pub const TEXTURE_FORMAT: TextureFormat = TextureFormat::Bgra8Unorm;
 
-#[allow(dead_code)]
-struct Vertex {
-    pos: [f32; 2], // "Position",
-    tex: [f32; 2], // "TexCoord",
-    col: [u8; 4],  // "Color",
+/// One vertex as nuklear writes it into the vertex buffer: 20 bytes, laid out as described by [`vertex_layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct Vertex {
+    pub pos: [f32; 2], // "Position",
+    pub tex: [f32; 2], // "TexCoord",
+    pub col: [u8; 4],  // "Color", RGBA
+}
+
+unsafe impl bytemuck::Zeroable for Vertex {}
+unsafe impl bytemuck::Pod for Vertex {}
+
+static VERTEX_ATTRIBUTES: [VertexAttributeDescriptor; 3] = [
+    VertexAttributeDescriptor {
+        offset: 0,
+        format: VertexFormat::Float2,
+        shader_location: 0,
+    },
+    VertexAttributeDescriptor {
+        offset: 8,
+        format: VertexFormat::Float2,
+        shader_location: 1,
+    },
+    VertexAttributeDescriptor {
+        offset: 16,
+        format: VertexFormat::Uchar4Norm,
+        shader_location: 2,
+    },
+];
+
+// Where nuklear writes each field of a `Vertex`, in the form `DrawVertexLayoutElements::new` takes.
+static NK_VERTEX_LAYOUT: [(DrawVertexLayoutAttribute, DrawVertexLayoutFormat, Size); 4] = [
+    (DrawVertexLayoutAttribute::Position, DrawVertexLayoutFormat::Float, 0),
+    (DrawVertexLayoutAttribute::TexCoord, DrawVertexLayoutFormat::Float, 8),
+    // Bytes in RGBA order, which the pipeline reads as `Uchar4Norm` into the shader's `vec4` color.
+    (DrawVertexLayoutAttribute::Color, DrawVertexLayoutFormat::R8G8B8A8, 16),
+    (DrawVertexLayoutAttribute::AttributeCount, DrawVertexLayoutFormat::Count, 0),
+];
+
+/// Vertex attributes of the built-in pipeline, for custom shaders and pipelines fed from the drawer's geometry:
+/// position and UV as `vec2` at locations 0 and 1, and the color as a normalized `vec4` at location 2.
+/// The stride is `size_of::<Vertex>()`.
+pub fn vertex_layout() -> &'static [VertexAttributeDescriptor] {
+    &VERTEX_ATTRIBUTES
 }
 #[allow(dead_code)]
 struct WgpuTexture {
-    texture: Texture,
-    sampler: Sampler,
+    // None for textures registered with `add_texture_raw`, which the caller owns.
+    texture: Option<Texture>,
+    // Kept with the sampler config and locals so the bind group can be recreated against a new layout.
+    view: TextureView,
+    sampler: SamplerConfig,
+    label: Option<String>,
     pub bind_group: BindGroup,
+    locals: Buffer,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureError {
+    MissingTexture(i32),
+    NotWritable(i32),
+    OutOfBounds,
+    DataSize { expected: usize, actual: usize },
+    UnsupportedFormat(TextureFormat),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawError {
+    MissingTexture(i32),
+    SampleCountMismatch,
+    DepthStencilMismatch,
+    PickTargetMismatch,
+    ReadbackFailed,
+}
+
+/// Failure to create a [`Drawer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DrawerError {
+    /// A built-in shader failed to compile at runtime (`glsl-runtime` feature), with glslang's diagnostics.
+    ShaderCompile(String),
+    /// The initial vertex or index buffer can't hold a single triangle.
+    BufferTooSmall { usage: BufferUsage, size: usize, minimum: usize },
+    /// A custom vertex layout has an attribute outside its stride, or a stride that isn't a positive multiple of 4.
+    VertexLayoutMismatch { stride: usize },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TextureError::MissingTexture(id) => write!(f, "no texture registered for handle id {}", id),
+            TextureError::NotWritable(id) => write!(f, "texture {} wraps an external texture and cannot be written", id),
+            TextureError::OutOfBounds => write!(f, "region lies outside the texture"),
+            TextureError::DataSize { expected, actual } => write!(f, "expected {} bytes of pixel data, got {}", expected, actual),
+            TextureError::UnsupportedFormat(format) => write!(f, "{:?} textures can't be uploaded", format),
+        }
+    }
+}
+
+impl Error for TextureError {}
+
+impl fmt::Display for DrawError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DrawError::MissingTexture(id) => write!(f, "a draw command uses unregistered texture id {}", id),
+            DrawError::SampleCountMismatch => write!(f, "a resolve target must be given exactly when the drawer is multisampled"),
+            DrawError::DepthStencilMismatch => write!(f, "depth view presence doesn't match the drawer's depth/stencil state"),
+            DrawError::PickTargetMismatch => write!(f, "a pick target must be given exactly when the drawer was built with picking"),
+            DrawError::ReadbackFailed => write!(f, "mapping the readback buffer failed"),
+        }
+    }
+}
+
+impl Error for DrawError {}
+
+impl fmt::Display for DrawerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DrawerError::ShaderCompile(ref log) => write!(f, "compiling a built-in shader failed: {}", log),
+            DrawerError::BufferTooSmall { usage, size, minimum } => write!(f, "{:?} buffer of {} bytes is below the minimum of {}", usage, size, minimum),
+            DrawerError::VertexLayoutMismatch { stride } => write!(f, "vertex layout doesn't fit its stride of {} bytes", stride),
+        }
+    }
+}
+
+impl Error for DrawerError {}
+
+/// Which buffers the last conversion overflowed. Overflowing buffers are grown and the frame is converted again, so
+/// geometry is never lost, but a set flag means the initial buffer sizes are too small for the UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConvertStatus {
+    pub vertex_overflow: bool,
+    pub element_overflow: bool,
+}
+
+/// Size of the UI drawn by the last [`Drawer::prepare`], after consecutive commands have been merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DrawStats {
+    pub draw_calls: usize,
+    pub vertices: usize,
+    pub indices: usize,
+    pub triangles: usize,
+}
+
+/// Sampler settings for an uploaded texture. Pixel-art fonts and icons should use `FilterMode::Nearest` to stay crisp.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerConfig {
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    /// Range of mip levels the sampler may pick from; raise the minimum to keep minified textures sharp.
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        SamplerConfig {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+        }
+    }
+}
+
+impl SamplerConfig {
+    pub fn nearest() -> Self {
+        SamplerConfig {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+
+    /// Tiles the texture on both axes, e.g. for repeating background patterns.
+    pub fn repeat() -> Self {
+        SamplerConfig {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            ..Default::default()
+        }
+    }
+}
+
+/// Options for a texture uploaded through [`Drawer::add_texture_with_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextureConfig {
+    pub sampler: SamplerConfig,
+    /// Generates a full mip chain on upload so scaled-down icons don't alias.
+    pub mipmaps: bool,
+}
+
+/// How rendered fragments are composited onto the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Colors are not multiplied by alpha; the default, matching nuklear's own output.
+    #[default]
+    StraightAlpha,
+    /// Colors are already multiplied by alpha, as in premultiplied font atlases and icons.
+    PremultipliedAlpha,
+}
+
+impl BlendMode {
+    fn color_blend(self) -> BlendDescriptor {
+        BlendDescriptor {
+            src_factor: match self {
+                BlendMode::StraightAlpha => BlendFactor::SrcAlpha,
+                BlendMode::PremultipliedAlpha => BlendFactor::One,
+            },
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        }
+    }
+
+    // Destination alpha accumulates coverage the same way in both modes ("over" compositing), so overlays on a
+    // transparent target end up with a sensible alpha.
+    fn alpha_blend(self) -> BlendDescriptor {
+        BlendDescriptor {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add,
+        }
+    }
+}
+
+/// What happens to the target's existing contents when `draw` begins its pass.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LoadBehavior {
+    /// Clears to the color, given in sRGB like nuklear's own colors.
+    Clear(Color),
+    /// Draws over the existing contents, for overlays on a target the caller has already cleared or drawn.
+    #[default]
+    Load,
+}
+
+impl From<Option<Color>> for LoadBehavior {
+    fn from(color: Option<Color>) -> Self {
+        color.map_or(LoadBehavior::Load, LoadBehavior::Clear)
+    }
 }
 
 type Ortho = [[f32; 4]; 4];
 
+// Dynamic uniform offsets must be multiples of 256 bytes, so each UI's `Locals` occupies a slot of this size.
+const LOCALS_STRIDE: u32 = 256;
+
+// Mirrors the `Locals` uniform block shared by vs.fx and ps.fx.
+#[allow(dead_code)]
+#[repr(C)]
+struct Locals {
+    proj: Ortho,
+    tint: [f32; 4],
+    gamma: f32,
+    y_scale: f32,
+    _pad: [f32; 2],
+}
+
 impl WgpuTexture {
-    pub fn new(device: &mut Device, queue: &mut Queue, drawer: &Drawer, image: &[u8], width: u32, height: u32) -> Self {
+    pub fn new(device: &Device, queue: &Queue, layout: &BindGroupLayout, sampler: &(SamplerConfig, Sampler), image: &[u8], width: u32, height: u32, format: TextureFormat, mipmaps: bool, label: Option<&str>) -> Self {
+        let mip_level_count = if mipmaps { mip_level_count(width, height) } else { 1 };
         let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: None,
+            label,
             size: Extent3d { width, height, depth: 1 },
             array_layer_count: 1,
             dimension: TextureDimension::D2,
-            format: TEXTURE_FORMAT,
-            mip_level_count: 1,
+            format,
+            mip_level_count,
             sample_count: 1,
             usage: TextureUsage::SAMPLED | TextureUsage::COPY_DST,
         });
 
-        let sampler = device.create_sampler(&SamplerDescriptor {
-            address_mode_u: AddressMode::ClampToEdge,
-            address_mode_v: AddressMode::ClampToEdge,
-            address_mode_w: AddressMode::ClampToEdge,
-            mag_filter: FilterMode::Linear,
-            min_filter: FilterMode::Linear,
-            mipmap_filter: FilterMode::Linear,
-            lod_min_clamp: -100.0,
-            lod_max_clamp: 100.0,
-            compare: CompareFunction::Always,
-        });
+        let view = texture.create_default_view();
+        let mut tex = WgpuTexture::from_view(device, layout, view, format, sampler, label);
+        tex.texture = Some(texture);
+        tex.width = width;
+        tex.height = height;
+        tex.write(device, queue, 0, 0, 0, width, height, image);
+
+        let (mut data, mut w, mut h) = (image.to_vec(), width, height);
+        for level in 1..mip_level_count {
+            data = downsample(&data, w, h, bytes_per_pixel(format) as usize);
+            w = (w / 2).max(1);
+            h = (h / 2).max(1);
+            tex.write(device, queue, level, 0, 0, w, h, &data);
+        }
+        tex
+    }
+
+    fn from_view(device: &Device, layout: &BindGroupLayout, view: TextureView, format: TextureFormat, sampler: &(SamplerConfig, Sampler), label: Option<&str>) -> Self {
+        // Single-channel textures hold coverage only, so the fragment shader reads their red channel as alpha.
+        let alpha_only: [u32; 4] = [(format == TextureFormat::R8Unorm) as u32, 0, 0, 0];
+        let locals = device.create_buffer_with_data(as_typed_slice(&alpha_only), BufferUsage::UNIFORM);
+
+        WgpuTexture {
+            bind_group: create_texture_group(device, layout, &view, &sampler.1, &locals, label),
+            texture: None,
+            view,
+            sampler: sampler.0,
+            label: label.map(str::to_string),
+            locals,
+            width: 0,
+            height: 0,
+            format,
+        }
+    }
 
-        let buffer = device.create_buffer_with_data(image, BufferUsage::COPY_SRC);
+    fn write(&self, device: &Device, queue: &Queue, level: u32, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
+        let texture = match self.texture {
+            Some(ref texture) => texture,
+            None => return,
+        };
+        let row = width * bytes_per_pixel(self.format);
+        let pitch = pad_to_256(row);
+        let buffer = if pitch == row {
+            device.create_buffer_with_data(data, BufferUsage::COPY_SRC)
+        } else {
+            device.create_buffer_with_data(&pad_rows(data, row as usize, pitch as usize), BufferUsage::COPY_SRC)
+        };
 
         let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
 
@@ -60,79 +375,351 @@ impl WgpuTexture {
             BufferCopyView {
                 buffer: &buffer,
                 offset: 0,
-                bytes_per_row: width * 4,
-                rows_per_image: height * 4,
+                bytes_per_row: pitch,
+                rows_per_image: height,
             },
             TextureCopyView {
-                texture: &texture,
-                mip_level: 0,
+                texture,
+                mip_level: level,
                 array_layer: 0,
-                origin: Origin3d::ZERO,
+                origin: Origin3d { x, y, z: 0 },
             },
             Extent3d { width, height, depth: 1 },
         );
 
         queue.submit(&[encoder.finish()]);
-
-        WgpuTexture {
-            bind_group: device.create_bind_group(&BindGroupDescriptor {
-                label: None,
-                layout: &drawer.tla,
-                bindings: &[
-                    wgpu::Binding {
-                        binding: 0,
-                        resource: BindingResource::TextureView(&texture.create_default_view()),
-                    },
-                    wgpu::Binding {
-                        binding: 1,
-                        resource: BindingResource::Sampler(&sampler),
-                    },
-                ],
-            }),
-            sampler,
-            texture,
-        }
     }
 }
 
+// One draw_indexed call recorded by `prepare` for `draw_into`.
+struct DrawCall {
+    texture: i32,
+    scissor: (u32, u32, u32, u32),
+    // Dynamic offset of the UI's `Locals` slot and the first vertex of its geometry.
+    locals: u32,
+    base_vertex: i32,
+    elements: Range<u32>,
+    // Written to the pick target; see `DrawerBuilder::picking`.
+    pick_id: u32,
+}
+
+/// Pixel rectangle of the target that one of the UIs passed to [`Drawer::draw_multi`], or the UI of a drawer given
+/// [`Drawer::set_viewport`], renders into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Converts nuklear UIs into wgpu draw calls; see [`DrawerBuilder`] for the options it is created with.
+///
+/// A `Drawer` is neither `Send` nor `Sync`. The nuklear command buffer it owns wraps an `nk_buffer`, which holds raw
+/// pointers to its storage and to the allocator passed to `NkBuffer::new`, and nothing guarantees that allocator may be
+/// used from another thread. Keep the drawer on the thread that owns the nuklear `Context`, which is bound there for
+/// the same reason; the `Device`, `Queue` and encoders are only borrowed per call and carry no such restriction here.
 pub struct Drawer {
     cmd: NkBuffer,
     pso: RenderPipeline,
+    wpo: Option<RenderPipeline>,
+    wfm: bool,
+    lbf: Option<Buffer>,
     tla: BindGroupLayout,
-    tex: Vec<WgpuTexture>,
+    tex: Vec<Option<WgpuTexture>>,
+    white: WgpuTexture,
+    smp: Vec<(SamplerConfig, Sampler)>,
+    ula: BindGroupLayout,
     ubf: Buffer,
     ubg: BindGroup,
+    uls: usize,
+    ulc: Vec<u8>,
+    vbf: Buffer,
+    ebf: Buffer,
+    vbd: Vec<u8>,
+    ebd: Vec<u8>,
     vsz: usize,
     esz: usize,
     vle: DrawVertexLayoutElements,
+    vst: usize,
+    calls: Vec<DrawCall>,
+    // Viewport and custom projection of each UI in the last prepared frame, and its scale, for `resize`.
+    prj: Vec<(Option<Viewport>, Option<Ortho>)>,
+    scl: Vec2,
+    tsz: (u32, u32),
+    rgn: Option<Viewport>,
+    vpt: Option<Viewport>,
+    cst: ConvertStatus,
+    sts: DrawStats,
+    tnt: [f32; 4],
+    gmm: f32,
+    fmt: TextureFormat,
+    msc: u32,
+    srg: bool,
+    fly: bool,
+    vld: bool,
+    lbl: Option<String>,
+    dsa: bool,
+    pik: bool,
+    lob: LoadBehavior,
+}
 
-    pub col: Option<Color>,
+/// Configures and creates a [`Drawer`].
+///
+/// Set `srgb` when rendering to an sRGB target, e.g. a winit surface whose swap chain uses `Bgra8UnormSrgb`.
+/// The pipeline then targets the sRGB variant of the target format and vertex colors are converted to linear before blending.
+///
+/// HDR pipelines can render the UI into an `Rgba16Float` target before tonemapping: pass it as the target format and
+/// set `srgb` as well, so vertex and clear colors are linearized for the float target. Blending then happens on
+/// unclamped linear values, so translucent widgets look lighter than on an 8-bit unorm target and a tint above 1.0
+/// produces HDR values instead of saturating.
+///
+/// A custom `fragment_shader` replaces the built-in `ps.fx` with caller-supplied SPIR-V. It must export a `main` entry point,
+/// read `vec2` UVs at location 0 and a `vec4` color at location 1, write a `vec4` to location 0, and may read the
+/// `Locals` block (`mat4` projection, `vec4` tint, `float` gamma, `float` Y scale) at set 0 binding 0 and sample
+/// `texture2D` at set 1 binding 0 with the `sampler` at set 1 binding 1. The uniform block at set 1 binding 2 holds a
+/// `uint` that is non-zero for single-channel textures. `srgb` has no effect on a custom shader. With
+/// [`DrawerBuilder::picking`] it must also write the flat `uint` at input location 2 to a `uint` output at location 1.
+#[derive(Clone)]
+pub struct DrawerBuilder<'a> {
+    load: LoadBehavior,
+    texture_count: usize,
+    vbo_size: usize,
+    ebo_size: usize,
+    format: TextureFormat,
+    srgb: bool,
+    sample_count: u32,
+    fragment_shader: Option<&'a [u32]>,
+    blend_mode: BlendMode,
+    depth_stencil: Option<DepthStencilStateDescriptor>,
+    depth_bias: (i32, f32, f32),
+    front_face: FrontFace,
+    cull_mode: CullMode,
+    picking: Option<TextureFormat>,
+    flip_y: bool,
+    vertex_layout: Option<CustomVertexLayout<'a>>,
+    label: Option<&'a str>,
+    validate: bool,
+    wireframe: bool,
 }
 
-impl Drawer {
-    pub fn new(device: &mut Device, col: Color, texture_count: usize, vbo_size: usize, ebo_size: usize, command_buffer: NkBuffer) -> Drawer {
-        let vs = include_bytes!("../shaders/vs.fx");
-        let fs = include_bytes!("../shaders/ps.fx");
-        let vs = device.create_shader_module(compile_glsl(from_utf8(vs).unwrap(), glsl_to_spirv::ShaderType::Vertex).as_slice());
-        let fs = device.create_shader_module(compile_glsl(from_utf8(fs).unwrap(), glsl_to_spirv::ShaderType::Fragment).as_slice());
+type CustomVertexLayout<'a> = (usize, &'a [(DrawVertexLayoutAttribute, DrawVertexLayoutFormat, Size)], &'a [VertexAttributeDescriptor]);
 
-        let ubf = device.create_buffer(&BufferDescriptor {
+impl<'a> Default for DrawerBuilder<'a> {
+    fn default() -> Self {
+        DrawerBuilder {
+            load: LoadBehavior::Load,
+            texture_count: 16,
+            vbo_size: 512 * 1024,
+            ebo_size: 128 * 1024,
+            format: TEXTURE_FORMAT,
+            srgb: false,
+            sample_count: 1,
+            fragment_shader: None,
+            blend_mode: BlendMode::default(),
+            depth_stencil: None,
+            depth_bias: (0, 0.0, 0.0),
+            front_face: FrontFace::Cw,
+            cull_mode: CullMode::None,
+            picking: None,
+            flip_y: true,
+            vertex_layout: None,
             label: None,
-            size: size_of::<Ortho>() as u64,
-            usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
-        });
+            validate: false,
+            wireframe: false,
+        }
+    }
+}
+
+impl<'a> DrawerBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear_color(mut self, col: Option<Color>) -> Self {
+        self.load = col.into();
+        self
+    }
+
+    pub fn load_behavior(mut self, load: LoadBehavior) -> Self {
+        self.load = load;
+        self
+    }
+
+    pub fn texture_capacity(mut self, count: usize) -> Self {
+        self.texture_count = count;
+        self
+    }
+
+    /// Initial size in bytes, 512 KiB by default; it grows when a frame doesn't fit, but each growth costs a second
+    /// conversion. Size it for the busiest expected UI, at 20 bytes per vertex (128 KiB per ~6500 vertices).
+    pub fn vertex_buffer_size(mut self, size: usize) -> Self {
+        self.vbo_size = size;
+        self
+    }
+
+    /// Initial size in bytes, 128 KiB by default, at 2 bytes per index; grows like the vertex buffer.
+    pub fn index_buffer_size(mut self, size: usize) -> Self {
+        self.ebo_size = size;
+        self
+    }
+
+    /// Format of the views passed to `draw`, which must match exactly or the pass fails validation. wgpu 0.5 has no
+    /// preferred-format query, so for a swap chain pass the `format` of the `SwapChainDescriptor` it was created with.
+    /// For an `*Srgb` swap chain pass the linear variant and set [`DrawerBuilder::srgb`] instead.
+    pub fn target_format(mut self, format: TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.sample_count = count;
+        self
+    }
+
+    pub fn fragment_shader(mut self, spv: &'a [u32]) -> Self {
+        self.fragment_shader = Some(spv);
+        self
+    }
+
+    pub fn blend_mode(mut self, mode: BlendMode) -> Self {
+        self.blend_mode = mode;
+        self
+    }
+
+    /// Lets the UI share a depth/stencil buffer with a 3D scene; `draw` must then be given a matching depth view.
+    pub fn depth_stencil(mut self, state: DepthStencilStateDescriptor) -> Self {
+        self.depth_stencil = Some(state);
+        self
+    }
+
+    /// Offsets the UI's depth to keep it from z-fighting coplanar scene geometry; only has an effect together with
+    /// [`DrawerBuilder::depth_stencil`]. Negative values pull the UI toward the camera with the usual `Less` compare.
+    pub fn depth_bias(mut self, constant: i32, slope_scale: f32, clamp: f32) -> Self {
+        self.depth_bias = (constant, slope_scale, clamp);
+        self
+    }
+
+    /// Winding of front-facing triangles and which faces to drop. Defaults to `Cw` without culling; nuklear mixes
+    /// windings in some shapes, so culling is only safe for geometry the caller controls.
+    pub fn culling(mut self, front_face: FrontFace, cull_mode: CullMode) -> Self {
+        self.front_face = front_face;
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Adds a second color attachment, e.g. `R32Uint`, that receives a per-command id for mouse picking. Each command
+    /// becomes its own draw call, and ids are the 1-based order of those calls in the last prepared frame: commands
+    /// without geometry or clipped away entirely get none, and 0 marks pixels where nothing was drawn. Render with
+    /// [`Drawer::draw_picking`]; the pick target is cleared every frame and, as integer targets can't be resolved,
+    /// picking needs a single-sampled drawer.
+    pub fn picking(mut self, format: TextureFormat) -> Self {
+        self.picking = Some(format);
+        self
+    }
+
+    /// Whether the vertex shader flips Y so nuklear's top-left origin lands at the top-left of the target, as wgpu
+    /// expects; on by default. Turn it off when rendering into a texture that will be flipped later, to avoid a double
+    /// flip: the UI, its scissors and `draw_multi` viewports are then mirrored to start at the bottom-left corner.
+    pub fn flip_y(mut self, flip_y: bool) -> Self {
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Replaces the built-in [`Vertex`] layout, e.g. to pad vertices shared with other pipelines. `nuklear` tells
+    /// nuklear where to write each attribute, in the form `DrawVertexLayoutElements::new` takes including the
+    /// `AttributeCount` terminator, and `attributes` tells the pipeline where to read them; both must fit in `stride`
+    /// bytes. Nuklear only writes position, UV and color, which the built-in vertex shader reads at locations 0, 1 and 2.
+    pub fn custom_vertex_layout(mut self, stride: usize, nuklear: &'a [(DrawVertexLayoutAttribute, DrawVertexLayoutFormat, Size)], attributes: &'a [VertexAttributeDescriptor]) -> Self {
+        self.vertex_layout = Some((stride, nuklear, attributes));
+        self
+    }
+
+    /// Prefixes the debug labels of the drawer's buffers, bind groups and built-in texture, so GPU debuggers such as
+    /// RenderDoc can tell them apart. wgpu 0.5 pipelines take no label.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Logs a warning for each problem `prepare` runs into, such as commands using unregistered textures, commands
+    /// clipped away entirely and frames without any draw calls, to help diagnose a blank UI. Toggle later with
+    /// [`Drawer::set_validation`].
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Also builds a `LineList` pipeline for debugging geometry, toggled with [`Drawer::set_wireframe`], that outlines
+    /// every triangle. `prepare` then spells out each triangle's three edges in an extra index buffer every frame;
+    /// [`Drawer::draw_prepared`] always draws filled.
+    pub fn wireframe(mut self, wireframe: bool) -> Self {
+        self.wireframe = wireframe;
+        self
+    }
+
+    /// Panics where [`DrawerBuilder::try_build`] would fail.
+    pub fn build(self, device: &Device, queue: &Queue, command_buffer: NkBuffer) -> Drawer {
+        self.try_build(device, queue, command_buffer).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_build(self, device: &Device, queue: &Queue, command_buffer: NkBuffer) -> Result<Drawer, DrawerError> {
+        let DrawerBuilder {
+            load,
+            texture_count,
+            vbo_size,
+            ebo_size,
+            format,
+            srgb,
+            sample_count,
+            fragment_shader,
+            blend_mode,
+            depth_stencil,
+            depth_bias,
+            front_face,
+            cull_mode,
+            picking,
+            flip_y,
+            vertex_layout: custom_layout,
+            label,
+            validate,
+            wireframe,
+        } = self;
 
+        let (stride, nk_layout, attributes) = custom_layout.unwrap_or((size_of::<Vertex>(), &NK_VERTEX_LAYOUT[..], vertex_layout()));
+
+        check_vertex_layout(stride, nk_layout, attributes)?;
+
+        check_buffer_sizes(stride, vbo_size, ebo_size)?;
+
+        let vs = device.create_shader_module(&vertex_spirv()?);
+        let fs = match fragment_shader {
+            Some(spv) => device.create_shader_module(spv),
+            None => device.create_shader_module(&fragment_spirv(srgb, picking.is_some())?),
+        };
+        let format = if srgb { srgb_format(format) } else { format };
+
+        let sub = |what| sublabel(label, what);
+        let ubf = create_uniform_buffer(device, 1, sub("uniforms").as_deref());
+
+        let ubg_label = sub("uniform layout");
         let ubg = BindGroupLayoutDescriptor {
-            label: None,
+            label: ubg_label.as_deref(),
             bindings: &[BindGroupLayoutEntry {
                 binding: 0,
-                visibility: ShaderStage::VERTEX,
-                ty: wgpu::BindingType::UniformBuffer { dynamic: false },
+                visibility: ShaderStage::VERTEX | ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer { dynamic: true },
             }],
         };
 
+        let tbg_label = sub("texture layout");
         let tbg = BindGroupLayoutDescriptor {
-            label: None,
+            label: tbg_label.as_deref(),
             bindings: &[
                 BindGroupLayoutEntry {
                     binding: 0,
@@ -140,7 +727,8 @@ impl Drawer {
                     ty: wgpu::BindingType::SampledTexture {
                         multisampled: false,
                         dimension: wgpu::TextureViewDimension::D2,
-                        component_type: TextureComponentType::Uint,
+                        // every format the drawer uploads or accepts is normalized, so it samples as float
+                        component_type: TextureComponentType::Float,
                     },
                 },
                 BindGroupLayoutEntry {
@@ -148,189 +736,2913 @@ impl Drawer {
                     visibility: ShaderStage::FRAGMENT,
                     ty: BindingType::Sampler { comparison: false },
                 },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStage::FRAGMENT,
+                    ty: BindingType::UniformBuffer { dynamic: false },
+                },
             ],
         };
 
         let tla = device.create_bind_group_layout(&tbg);
         let ula = device.create_bind_group_layout(&ubg);
 
-        Drawer {
-            cmd: command_buffer,
-            col: Some(col),
-            pso: device.create_render_pipeline(&RenderPipelineDescriptor {
-                layout: &device.create_pipeline_layout(&PipelineLayoutDescriptor { bind_group_layouts: &[&ula, &tla] }),
+        // Bound for commands with a null texture handle, so untextured shapes render with their vertex color.
+        let mut smp = Vec::new();
+        let white = WgpuTexture::new(device, queue, &tla, shared_sampler(&mut smp, device, &SamplerConfig::default()), &[0xff; 4], 1, 1, TEXTURE_FORMAT, false, sub("white").as_deref());
+
+        let mut color_states = vec![ColorStateDescriptor {
+            format,
+            color_blend: blend_mode.color_blend(),
+            alpha_blend: blend_mode.alpha_blend(),
+            write_mask: ColorWrite::ALL,
+        }];
+        // Ids are overwritten rather than blended, which integer targets don't support anyway.
+        color_states.extend(picking.map(|format| ColorStateDescriptor {
+            format,
+            color_blend: BlendDescriptor::REPLACE,
+            alpha_blend: BlendDescriptor::REPLACE,
+            write_mask: ColorWrite::ALL,
+        }));
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor { bind_group_layouts: &[&ula, &tla] });
+        let pipeline = |primitive_topology| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                layout: &layout,
                 vertex_stage: ProgrammableStageDescriptor { module: &vs, entry_point: "main" },
                 fragment_stage: Some(ProgrammableStageDescriptor { module: &fs, entry_point: "main" }),
                 rasterization_state: Some(RasterizationStateDescriptor {
-                    front_face: FrontFace::Cw,
-                    cull_mode: CullMode::None,
-                    depth_bias: 0,
-                    depth_bias_slope_scale: 0.0,
-                    depth_bias_clamp: 0.0,
+                    front_face,
+                    cull_mode,
+                    depth_bias: depth_bias.0,
+                    depth_bias_slope_scale: depth_bias.1,
+                    depth_bias_clamp: depth_bias.2,
                 }),
-                primitive_topology: PrimitiveTopology::TriangleList,
-                color_states: &[ColorStateDescriptor {
-                    format: TEXTURE_FORMAT,
-                    color_blend: BlendDescriptor {
-                        src_factor: BlendFactor::SrcAlpha,
-                        dst_factor: BlendFactor::OneMinusSrcAlpha,
-                        operation: BlendOperation::Add,
-                    },
-                    alpha_blend: BlendDescriptor {
-                        src_factor: BlendFactor::OneMinusDstAlpha,
-                        dst_factor: BlendFactor::One,
-                        operation: BlendOperation::Add,
-                    },
-                    write_mask: ColorWrite::ALL,
-                }],
-                depth_stencil_state: None,
+                primitive_topology,
+                color_states: &color_states,
+                depth_stencil_state: depth_stencil.clone(),
                 vertex_state: VertexStateDescriptor {
                     index_format: IndexFormat::Uint16,
                     vertex_buffers: &[VertexBufferDescriptor {
-                        stride: (size_of::<Vertex>()) as u64,
+                        stride: stride as u64,
                         step_mode: InputStepMode::Vertex,
-                        attributes: &vertex_attr_array![ 0 => Float2, 1 => Float2, 2 => Uint ],
+                        attributes,
                     }],
                 },
-                sample_count: 1,
+                sample_count,
                 sample_mask: !0,
                 alpha_to_coverage_enabled: false,
-            }),
+            })
+        };
+
+        Ok(Drawer {
+            cmd: command_buffer,
+            lob: load,
+            pso: pipeline(PrimitiveTopology::TriangleList),
+            wpo: if wireframe { Some(pipeline(PrimitiveTopology::LineList)) } else { None },
+            wfm: wireframe,
+            lbf: None,
             tex: Vec::with_capacity(texture_count + 1),
-            ubg: device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &ula,
-                bindings: &[Binding {
-                    binding: 0,
-                    resource: BindingResource::Buffer {
-                        buffer: &ubf,
-                        range: 0..(size_of::<Ortho>() as u64),
-                    },
-                }],
-            }),
-            vle: DrawVertexLayoutElements::new(&[
-                (DrawVertexLayoutAttribute::Position, DrawVertexLayoutFormat::Float, 0),
-                (DrawVertexLayoutAttribute::TexCoord, DrawVertexLayoutFormat::Float, size_of::<f32>() as Size * 2),
-                (DrawVertexLayoutAttribute::Color, DrawVertexLayoutFormat::B8G8R8A8, size_of::<f32>() as Size * 4),
-                (DrawVertexLayoutAttribute::AttributeCount, DrawVertexLayoutFormat::Count, 0),
-            ]),
+            white,
+            smp,
+            ubg: create_uniform_group(device, &ula, &ubf, sub("uniforms").as_deref()),
+            uls: 1,
+            ulc: Vec::new(),
+            vle: DrawVertexLayoutElements::new(nk_layout),
+            vst: stride,
+            calls: Vec::new(),
+            prj: Vec::new(),
+            scl: Vec2 { x: 1.0, y: 1.0 },
+            tsz: (0, 0),
+            rgn: None,
+            vpt: None,
+            cst: ConvertStatus::default(),
+            sts: DrawStats::default(),
+            tnt: [1.0; 4],
+            gmm: 1.0,
+            fmt: format,
+            srg: srgb,
+            fly: flip_y,
+            vld: validate,
+            lbl: label.map(str::to_string),
+            msc: sample_count,
+            dsa: depth_stencil.is_some(),
+            pik: picking.is_some(),
+            vbf: create_geometry_buffer(device, vbo_size, BufferUsage::VERTEX, sub("vertices").as_deref()),
+            ebf: create_geometry_buffer(device, ebo_size, BufferUsage::INDEX, sub("indices").as_deref()),
+            vbd: vec![0; vbo_size],
+            ebd: vec![0; ebo_size],
             vsz: vbo_size,
             esz: ebo_size,
             ubf,
+            ula,
             tla,
+        })
+    }
+}
+
+impl Drawer {
+    /// Positional shorthand for [`DrawerBuilder`], which documents each option.
+    pub fn new(device: &Device, queue: &Queue, col: Color, texture_count: usize, vbo_size: usize, ebo_size: usize, command_buffer: NkBuffer, format: TextureFormat, srgb: bool, sample_count: u32, fragment_shader: Option<&[u32]>) -> Drawer {
+        DrawerBuilder {
+            load: LoadBehavior::Clear(col),
+            texture_count,
+            vbo_size,
+            ebo_size,
+            format,
+            srgb,
+            sample_count,
+            fragment_shader,
+            ..Default::default()
         }
+        .build(device, queue, command_buffer)
     }
 
-    pub fn add_texture(&mut self, device: &mut Device, queue: &mut Queue, image: &[u8], width: u32, height: u32) -> Handle {
-        self.tex.push(WgpuTexture::new(device, queue, self, image, width, height));
-        Handle::from_id(self.tex.len() as i32)
+    /// Format of the render target the pipeline was built for.
+    pub fn target_format(&self) -> TextureFormat {
+        self.fmt
     }
 
-    pub fn draw(&mut self, ctx: &mut Context, cfg: &mut ConvertConfig, encoder: &mut CommandEncoder, view: &TextureView, device: &mut Device, width: u32, height: u32, scale: Vec2) {
-        let ortho: Ortho = [
-            [2.0f32 / width as f32, 0.0f32, 0.0f32, 0.0f32],
-            [0.0f32, -2.0f32 / height as f32, 0.0f32, 0.0f32],
-            [0.0f32, 0.0f32, -1.0f32, 0.0f32],
-            [-1.0f32, 1.0f32, 0.0f32, 1.0f32],
-        ];
-        let ubf_size = size_of_val(&ortho);
-        cfg.set_vertex_layout(&self.vle);
-        cfg.set_vertex_size(size_of::<Vertex>());
+    pub fn sample_count(&self) -> u32 {
+        self.msc
+    }
 
-        //TODO: replace these with proper staging buffers.
-        let mut vbf = device.create_buffer_mapped(&BufferDescriptor {
-            label: None,
-            size: self.vsz as u64,
-            usage: BufferUsage::VERTEX | BufferUsage::COPY_SRC,
-        });
+    /// The filled pipeline `draw_into` binds, for reuse in a caller's own pass. Its bind group 0 is
+    /// [`Drawer::uniform_bind_group`], taking a dynamic offset of 0 for single-UI frames, and group 1 a texture's.
+    pub fn pipeline(&self) -> &RenderPipeline {
+        &self.pso
+    }
 
-        let mut ebf = device.create_buffer_mapped(&BufferDescriptor {
-            label: None,
-            size: self.esz as u64,
-            usage: BufferUsage::INDEX | BufferUsage::COPY_SRC,
-        });
+    pub fn uniform_bind_group(&self) -> &BindGroup {
+        &self.ubg
+    }
 
-        let ubf = device.create_buffer_with_data(as_typed_slice(&ortho), BufferUsage::UNIFORM | BufferUsage::COPY_SRC);
+    /// Multiplies the color of the whole UI, e.g. `[1.0, 1.0, 1.0, 0.5]` to fade it to half opacity. Defaults to opaque white.
+    pub fn set_tint(&mut self, rgba: [f32; 4]) {
+        self.tnt = rgba;
+    }
 
-        {
-            let mut vbuf = NkBuffer::with_fixed(&mut vbf.data);
-            let mut ebuf = NkBuffer::with_fixed(&mut ebf.data);
+    /// Brightens (above 1.0) or darkens (below 1.0) the whole UI by raising its output color to `1 / gamma`, e.g. for
+    /// low-vision users, without touching the style. Clamped to `0.2..=5.0`, with NaN resetting to the default of 1.0,
+    /// which changes nothing.
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gmm = if gamma.is_nan() { 1.0 } else { gamma.clamp(0.2, 5.0) };
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.gmm
+    }
 
-            ctx.convert(&mut self.cmd, &mut vbuf, &mut ebuf, cfg);
+    /// Embeds the UI in a sub-rectangle of the target, e.g. an editor panel: it is laid out in logical units from the
+    /// viewport's top-left corner and clipped to it, while the rest of the target is only touched by a clear. A custom
+    /// `projection` passed to `draw` replaces the offset but is still clipped. `None` fills the whole target again.
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.vpt = viewport;
+    }
 
-            let vbf = unsafe { std::slice::from_raw_parts_mut(vbf.data as *mut _ as *mut Vertex, vbf.data.len() / std::mem::size_of::<Vertex>()) };
+    pub fn viewport(&self) -> Option<Viewport> {
+        self.vpt
+    }
 
-            for v in vbf.iter_mut() {
-                v.pos[1] = height as f32 - v.pos[1];
-            }
+    /// Limits rendering to a damaged pixel region of the target, e.g. the area nuklear reports as changed, so idle
+    /// frames cost less. Commands are clipped to it and those entirely outside are skipped. Pair it with
+    /// [`LoadBehavior::Load`], since a clear still covers the whole target. `None` renders everything again.
+    pub fn set_render_region(&mut self, region: Option<Viewport>) {
+        self.rgn = region;
+    }
+
+    pub fn render_region(&self) -> Option<Viewport> {
+        self.rgn
+    }
+
+    /// Color `draw` clears the target to when the call doesn't pass its own; `None` draws over the existing contents.
+    pub fn set_clear_color(&mut self, color: Option<Color>) {
+        self.lob = color.into();
+    }
+
+    pub fn clear_color(&self) -> Option<Color> {
+        match self.lob {
+            LoadBehavior::Clear(color) => Some(color),
+            LoadBehavior::Load => None,
         }
-        let vbf = vbf.finish();
-        let ebf = ebf.finish();
+    }
 
-        encoder.copy_buffer_to_buffer(&ubf, 0, &self.ubf, 0, ubf_size as u64);
+    pub fn set_load_behavior(&mut self, load: LoadBehavior) {
+        self.lob = load;
+    }
 
-        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
-            color_attachments: &[RenderPassColorAttachmentDescriptor {
-                attachment: &view,
-                load_op: match self.col {
-                    Some(_) => wgpu::LoadOp::Clear,
-                    _ => wgpu::LoadOp::Load,
-                },
-                resolve_target: None,
-                store_op: StoreOp::Store,
-                clear_color: self.col.unwrap_or(Color { r: 1.0, g: 2.0, b: 3.0, a: 1.0 }),
-            }],
-            depth_stencil_attachment: None,
-        });
-        rpass.set_pipeline(&self.pso);
+    pub fn load_behavior(&self) -> LoadBehavior {
+        self.lob
+    }
 
-        rpass.set_vertex_buffer(0, &vbf, 0, 0);
-        rpass.set_index_buffer(&ebf, 0, 0);
+    pub fn set_validation(&mut self, validate: bool) {
+        self.vld = validate;
+    }
 
-        rpass.set_bind_group(0, &self.ubg, &[]);
+    /// Switches between filled and line rendering from the next `prepare`; ignored unless the drawer was built with
+    /// [`DrawerBuilder::wireframe`].
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wfm = wireframe;
+    }
 
-        let mut start = 0;
-        let mut end;
+    /// Points `cfg` at the drawer's vertex layout and size. `prepare` applies this to whatever config it is given, since
+    /// the layout lives in the drawer and a config last used with another (possibly dropped) drawer would point there.
+    pub fn configure(&self, cfg: &mut ConvertConfig) {
+        cfg.set_vertex_layout(&self.vle);
+        cfg.set_vertex_size(self.vertex_size());
+    }
 
-        for cmd in ctx.draw_command_iterator(&self.cmd) {
-            if cmd.elem_count() < 1 {
-                continue;
-            }
+    /// Bytes per vertex nuklear writes: `size_of::<Vertex>()` unless built with [`DrawerBuilder::custom_vertex_layout`].
+    pub fn vertex_size(&self) -> usize {
+        self.vst
+    }
+
+    /// Bytes per index; nuklear emits `u16` indices, drawn with `IndexFormat::Uint16`.
+    pub fn index_size(&self) -> usize {
+        size_of::<u16>()
+    }
+
+    /// Overflow flags from the most recent [`Drawer::prepare`].
+    pub fn last_convert_status(&self) -> ConvertStatus {
+        self.cst
+    }
 
-            let id = cmd.texture().id().unwrap();
-            let res = self.find_res(id).unwrap();
+    pub fn last_stats(&self) -> DrawStats {
+        self.sts
+    }
 
-            end = start + cmd.elem_count();
+    /// Current vertex buffer size in bytes; grows when a frame doesn't fit.
+    pub fn vertex_buffer_size(&self) -> usize {
+        self.vsz
+    }
 
-            rpass.set_bind_group(1, &res.bind_group, &[]);
+    /// Current index buffer size in bytes; grows when a frame doesn't fit.
+    pub fn index_buffer_size(&self) -> usize {
+        self.esz
+    }
 
-            let nuklear::Rect { x, y, w, h } = cmd.clip_rect();
-            rpass.set_scissor_rect((x * scale.x) as u32, (y * scale.y) as u32, (w * scale.x) as u32, (h * scale.y) as u32);
+    /// Number of registered textures, not counting removed ones.
+    pub fn texture_count(&self) -> usize {
+        self.tex.iter().filter(|t| t.is_some()).count()
+    }
 
-            rpass.draw_indexed(start..end, 0 as i32, 0..1);
+    /// Records a new target size in pixels, e.g. from a window resize. `prepare` records the size it is given too, so
+    /// this matters when the target changes between `prepare` and [`Drawer::draw_into`]: the prepared projections are
+    /// recomputed for it and uploaded through `encoder`, prepared scissors are clamped to it and the pass's default
+    /// scissor is assumed to cover it. Custom projections passed to `prepare` are kept as they are.
+    pub fn resize(&mut self, device: &Device, encoder: &mut CommandEncoder, width: u32, height: u32) {
+        self.tsz = (width, height);
+        for call in &mut self.calls {
+            let (x, y, w, h) = call.scissor;
+            let (x, y) = (x.min(width), y.min(height));
+            call.scissor = (x, y, w.min(width - x), h.min(height - y));
+        }
+        self.calls.retain(|call| call.scissor.2 > 0 && call.scissor.3 > 0);
 
-            start = end;
+        if width > 0 && height > 0 && !self.prj.is_empty() {
+            let projs = self.projections(width, height);
+            self.upload_locals(device, encoder, &projs);
         }
     }
 
-    fn find_res(&self, id: i32) -> Option<&WgpuTexture> {
-        if id > 0 && id as usize <= self.tex.len() {
-            self.tex.get((id - 1) as usize)
-        } else {
-            None
-        }
+    /// Target size in pixels from the last [`Drawer::resize`] or `prepare`.
+    pub fn target_size(&self) -> (u32, u32) {
+        self.tsz
     }
-}
 
-fn as_typed_slice<T>(data: &[T]) -> &[u8] {
-    unsafe { from_raw_parts(data.as_ptr() as *const u8, data.len() * size_of::<T>()) }
-}
-fn compile_glsl(code: &str, ty: glsl_to_spirv::ShaderType) -> Vec<u32> {
-    let mut output = glsl_to_spirv::compile(code, ty).unwrap();
-    let mut spv = Vec::new();
-    output.read_to_end(&mut spv).unwrap();
+    /// Number of distinct samplers created so far; textures uploaded with equal [`SamplerConfig`]s share one.
+    pub fn sampler_count(&self) -> usize {
+        self.smp.len()
+    }
+
+    /// Handles of the registered textures in slot order, skipping removed ones.
+    pub fn texture_handles(&self) -> impl Iterator<Item = Handle> + '_ {
+        self.tex.iter().enumerate().filter(|(_, t)| t.is_some()).map(|(i, _)| Handle::from_id(i as i32 + 1))
+    }
 
-    let spv32: Vec<u32> = unsafe { Vec::from_raw_parts(spv.as_mut_ptr() as *mut _ as *mut u32, spv.len() / 4, spv.capacity() / 4) };
-    forget(spv);
-    spv32
+    /// Number of texture slots that fit before the texture list reallocates.
+    pub fn texture_capacity(&self) -> usize {
+        self.tex.capacity()
+    }
+
+    /// Empties the nuklear command buffer that `prepare` converts into. `prepare` does this itself before converting, so
+    /// commands from a previous frame are never drawn twice; calling it directly only discards the last frame early.
+    /// The per-frame order is: build the UI, `draw` (or `prepare` + `draw_into`), then `ctx.clear()`.
+    pub fn clear_commands(&mut self) {
+        clear_buffer(&mut self.cmd);
+    }
+
+    /// Makes room for `additional` more textures before a bulk load. Handles are slot indices, not addresses, so they
+    /// stay valid whether or not the list reallocates.
+    pub fn reserve_textures(&mut self, additional: usize) {
+        self.tex.reserve(additional);
+    }
+
+    /// Uploads a BGRA8 image (see [`TEXTURE_FORMAT`]); use [`Drawer::add_texture_rgba`] for RGBA8 data such as nuklear's font atlas.
+    pub fn add_texture(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32) -> Handle {
+        self.add_texture_with_sampler(device, queue, image, width, height, SamplerConfig::default())
+    }
+
+    pub fn add_texture_with_sampler(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32, sampler: SamplerConfig) -> Handle {
+        self.add_texture_with_config(device, queue, image, width, height, TextureConfig { sampler, ..Default::default() })
+    }
+
+    pub fn add_texture_with_config(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32, config: TextureConfig) -> Handle {
+        self.push_texture(device, queue, image, width, height, TEXTURE_FORMAT, config, None)
+    }
+
+    /// Uploads an image in any of the 8-bit normalized formats the fragment shader can sample: `Bgra8Unorm`,
+    /// `Rgba8Unorm`, their `*Srgb` variants (decoded to linear when sampled, e.g. for photos) and `R8Unorm`, which is
+    /// drawn like [`Drawer::add_texture_r8`]. Textures of different formats can be mixed freely in one frame.
+    pub fn add_texture_with_format(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32, format: TextureFormat, config: TextureConfig) -> Result<Handle, TextureError> {
+        match format {
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb | TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb | TextureFormat::R8Unorm => {}
+            _ => return Err(TextureError::UnsupportedFormat(format)),
+        }
+        let expected = width as usize * height as usize * bytes_per_pixel(format) as usize;
+        if image.len() != expected {
+            return Err(TextureError::DataSize { expected, actual: image.len() });
+        }
+        Ok(self.push_texture(device, queue, image, width, height, format, config, None))
+    }
+
+    /// [`Drawer::add_texture_with_config`] with a debug label on the texture and its bind group, shown by GPU debuggers.
+    pub fn add_texture_labeled(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32, config: TextureConfig, label: &str) -> Handle {
+        self.push_texture(device, queue, image, width, height, TEXTURE_FORMAT, config, Some(label))
+    }
+
+    fn push_texture(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32, format: TextureFormat, config: TextureConfig, label: Option<&str>) -> Handle {
+        let sampler = shared_sampler(&mut self.smp, device, &config.sampler);
+        self.tex.push(Some(WgpuTexture::new(device, queue, &self.tla, sampler, image, width, height, format, config.mipmaps, label)));
+        Handle::from_id(self.tex.len() as i32)
+    }
+
+    /// Uploads a straight-alpha BGRA8 image with its color multiplied by alpha, for drawing with
+    /// [`BlendMode::PremultipliedAlpha`] without dark halos around transparent edges.
+    pub fn add_texture_premultiply(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32) -> Handle {
+        let mut data = image.to_vec();
+        premultiply(&mut data);
+        self.add_texture(device, queue, &data, width, height)
+    }
+
+    /// Uploads an RGBA8 image as-is, without swapping the red and blue channels.
+    pub fn add_texture_rgba(&mut self, device: &Device, queue: &Queue, image: &[u8], width: u32, height: u32) -> Handle {
+        self.push_texture(device, queue, image, width, height, TextureFormat::Rgba8Unorm, TextureConfig::default(), None)
+    }
+
+    /// Uploads an `image` crate buffer. It goes through [`Drawer::add_texture_rgba`], so no channel swizzle is needed.
+    #[cfg(feature = "image-integration")]
+    pub fn add_texture_image(&mut self, device: &Device, queue: &Queue, img: &image::RgbaImage) -> Handle {
+        self.add_texture_rgba(device, queue, img.as_raw(), img.width(), img.height())
+    }
+
+    /// Uploads a single-channel coverage image, such as a baked font atlas, at a quarter of the memory of an RGBA one.
+    /// It is drawn as white with the red channel used as alpha, so vertex colors tint it.
+    pub fn add_texture_r8(&mut self, device: &Device, queue: &Queue, data: &[u8], width: u32, height: u32) -> Handle {
+        self.push_texture(device, queue, data, width, height, TextureFormat::R8Unorm, TextureConfig::default(), None)
+    }
+
+    /// Registers a texture the caller already has on the GPU, e.g. a video frame or a render target, without a CPU copy.
+    /// The caller keeps ownership of `texture`; `format` must be the format it was created with. Such textures can't be
+    /// updated through [`Drawer::update_texture_region`].
+    pub fn add_texture_raw(&mut self, device: &Device, texture: &Texture, format: TextureFormat, sampler: SamplerConfig) -> Handle {
+        let view = texture.create_default_view();
+        let sampler = shared_sampler(&mut self.smp, device, &sampler);
+        self.tex.push(Some(WgpuTexture::from_view(device, &self.tla, view, format, sampler, None)));
+        Handle::from_id(self.tex.len() as i32)
+    }
+
+    /// Recreates the bind group of every registered texture, and of the built-in white one, against the drawer's
+    /// current texture bind group layout. Needed after anything replaces that layout, since existing groups go stale.
+    pub fn rebuild_texture_bind_groups(&mut self, device: &Device) {
+        let (tla, smp) = (&self.tla, &mut self.smp);
+        for tex in self.tex.iter_mut().filter_map(Option::as_mut).chain(Some(&mut self.white)) {
+            let sampler = shared_sampler(smp, device, &tex.sampler);
+            tex.bind_group = create_texture_group(device, tla, &tex.view, &sampler.1, &tex.locals, tex.label.as_deref());
+        }
+    }
+
+    /// Releases the GPU resources behind `handle`. The slot stays reserved, so handles of other textures remain valid.
+    pub fn remove_texture(&mut self, mut handle: Handle) {
+        if let Some(id) = handle.id() {
+            if id > 0 && id as usize <= self.tex.len() {
+                self.tex[(id - 1) as usize] = None;
+            }
+        }
+    }
+
+    /// Releases every registered texture at once, e.g. before reloading a theme. All outstanding handles become invalid
+    /// and the next texture added gets id 1 again, so stale handles may silently refer to new textures.
+    pub fn clear_textures(&mut self) {
+        self.tex.clear();
+    }
+
+    /// Renders the UI into `view` in its own render pass; see [`Drawer::prepare`] for `width`, `height`, `scale` and
+    /// `projection`.
+    ///
+    /// `clear` clears to that color for this frame only; pass `None` to keep the drawer's [`LoadBehavior`].
+    ///
+    /// A multisampled drawer renders into a multisampled `view` and must be given a `resolve_target`; a single-sampled one must not.
+    /// Likewise `depth_stencil` must be given exactly when the drawer was built with [`DrawerBuilder::depth_stencil`].
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        cfg: &mut ConvertConfig,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        resolve_target: Option<&TextureView>,
+        depth_stencil: Option<&TextureView>,
+        device: &Device,
+        width: u32,
+        height: u32,
+        scale: Vec2,
+        clear: Option<Color>,
+        projection: Option<[[f32; 4]; 4]>,
+    ) -> Result<(), DrawError> {
+        self.check_targets(resolve_target, depth_stencil, None)?;
+        self.prepare(ctx, cfg, encoder, device, width, height, scale, projection)?;
+        self.record(encoder, view, resolve_target, depth_stencil, None, clear.map_or(self.lob, LoadBehavior::Clear));
+        Ok(())
+    }
+
+    /// [`Drawer::draw`] for a drawer built with [`DrawerBuilder::picking`], also writing command ids into `pick_target`,
+    /// which must be in the picking format and the size of `view`.
+    pub fn draw_picking(
+        &mut self,
+        ctx: &mut Context,
+        cfg: &mut ConvertConfig,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        pick_target: &TextureView,
+        depth_stencil: Option<&TextureView>,
+        device: &Device,
+        width: u32,
+        height: u32,
+        scale: Vec2,
+        clear: Option<Color>,
+        projection: Option<[[f32; 4]; 4]>,
+    ) -> Result<(), DrawError> {
+        self.check_targets(None, depth_stencil, Some(pick_target))?;
+        self.prepare(ctx, cfg, encoder, device, width, height, scale, projection)?;
+        self.record(encoder, view, None, depth_stencil, Some(pick_target), clear.map_or(self.lob, LoadBehavior::Clear));
+        Ok(())
+    }
+
+    /// Renders several independent UIs, e.g. split-screen players, in one pass. Each UI is laid out in logical units
+    /// from the top-left corner of its [`Viewport`] and clipped to it; the other parameters are as for [`Drawer::draw`].
+    pub fn draw_multi(
+        &mut self,
+        uis: &mut [(&mut Context, Viewport)],
+        cfg: &mut ConvertConfig,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        resolve_target: Option<&TextureView>,
+        depth_stencil: Option<&TextureView>,
+        device: &Device,
+        width: u32,
+        height: u32,
+        scale: Vec2,
+        clear: Option<Color>,
+    ) -> Result<(), DrawError> {
+        self.check_targets(resolve_target, depth_stencil, None)?;
+        self.prepare_multi(uis, cfg, encoder, device, width, height, scale)?;
+        self.record(encoder, view, resolve_target, depth_stencil, None, clear.map_or(self.lob, LoadBehavior::Clear));
+        Ok(())
+    }
+
+    fn check_targets(&self, resolve_target: Option<&TextureView>, depth_stencil: Option<&TextureView>, pick_target: Option<&TextureView>) -> Result<(), DrawError> {
+        if resolve_target.is_some() != (self.msc > 1) {
+            return Err(DrawError::SampleCountMismatch);
+        }
+        if depth_stencil.is_some() != self.dsa {
+            return Err(DrawError::DepthStencilMismatch);
+        }
+        if pick_target.is_some() != self.pik {
+            return Err(DrawError::PickTargetMismatch);
+        }
+        Ok(())
+    }
+
+    // Begins the pass for `draw` and `draw_multi` and records the prepared calls into it.
+    fn record(&self, encoder: &mut CommandEncoder, view: &TextureView, resolve_target: Option<&TextureView>, depth_stencil: Option<&TextureView>, pick_target: Option<&TextureView>, load: LoadBehavior) {
+        let (load_op, clear_color) = match load {
+            // Clear colors are given in sRGB like vertex colors, but an sRGB attachment expects them linear.
+            LoadBehavior::Clear(c) if self.srg => (
+                LoadOp::Clear,
+                Color {
+                    r: srgb_to_linear(c.r),
+                    g: srgb_to_linear(c.g),
+                    b: srgb_to_linear(c.b),
+                    a: c.a,
+                },
+            ),
+            LoadBehavior::Clear(c) => (LoadOp::Clear, c),
+            // A hidden UI needs no pass at all unless the target, or a pick target left with last frame's ids, still
+            // has to be cleared.
+            LoadBehavior::Load if self.calls.is_empty() && pick_target.is_none() => return,
+            // wgpu 0.5 always takes a clear color; it is ignored when loading.
+            LoadBehavior::Load => (LoadOp::Load, Color::TRANSPARENT),
+        };
+
+        let mut color_attachments = vec![RenderPassColorAttachmentDescriptor {
+            attachment: view,
+            load_op,
+            resolve_target,
+            store_op: StoreOp::Store,
+            clear_color,
+        }];
+        // Ids only describe this frame, so the pick target always starts from 0 ("nothing").
+        color_attachments.extend(pick_target.map(|attachment| RenderPassColorAttachmentDescriptor {
+            attachment,
+            load_op: LoadOp::Clear,
+            resolve_target: None,
+            store_op: StoreOp::Store,
+            clear_color: Color::TRANSPARENT,
+        }));
+
+        let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &color_attachments,
+            // The UI is tested against the scene's existing depth and stencil contents rather than clearing them.
+            depth_stencil_attachment: depth_stencil.map(|attachment| RenderPassDepthStencilAttachmentDescriptor {
+                attachment,
+                depth_load_op: LoadOp::Load,
+                depth_store_op: StoreOp::Store,
+                clear_depth: 1.0,
+                stencil_load_op: LoadOp::Load,
+                stencil_store_op: StoreOp::Store,
+                clear_stencil: 0,
+            }),
+        });
+        // A fresh pass starts with a scissor covering the whole target, which full-screen clip rects clamp to.
+        self.record_calls(&mut rpass, Some((0, 0, self.tsz.0, self.tsz.1)));
+    }
+
+    /// Converts the UI and records the geometry and uniform uploads into `encoder`, ready for [`Drawer::draw_into`].
+    ///
+    /// `width` and `height` are the physical size of the target in pixels, and `scale` is the content scale (e.g. 2.0 on a
+    /// Retina display). Nuklear works in logical units, so a widget at logical (100, 100) lands at physical (200, 200)
+    /// with a scale of 2.
+    ///
+    /// `projection` replaces the column-major orthographic matrix for this frame, e.g. to offset the UI. Like the default,
+    /// it should map logical (0, 0) to (-1, -1) and the logical size to (1, 1); the vertex shader flips Y afterwards
+    /// unless disabled with [`DrawerBuilder::flip_y`].
+    pub fn prepare(&mut self, ctx: &mut Context, cfg: &mut ConvertConfig, encoder: &mut CommandEncoder, device: &Device, width: u32, height: u32, scale: Vec2, projection: Option<[[f32; 4]; 4]>) -> Result<(), DrawError> {
+        let viewport = self.vpt;
+        self.prepare_uis(&mut [(ctx, viewport, projection)], cfg, encoder, device, width, height, scale)
+    }
+
+    /// [`Drawer::prepare`] for the UIs of [`Drawer::draw_multi`], for use with a caller-managed pass.
+    pub fn prepare_multi(&mut self, uis: &mut [(&mut Context, Viewport)], cfg: &mut ConvertConfig, encoder: &mut CommandEncoder, device: &Device, width: u32, height: u32, scale: Vec2) -> Result<(), DrawError> {
+        let mut uis: Vec<_> = uis.iter_mut().map(|&mut (ref mut ctx, viewport)| (&mut **ctx, Some(viewport), None)).collect();
+        self.prepare_uis(&mut uis, cfg, encoder, device, width, height, scale)
+    }
+
+    // Each UI comes with its viewport, `None` for the whole target, and its custom projection if any.
+    fn prepare_uis(&mut self, uis: &mut [(&mut Context, Option<Viewport>, Option<Ortho>)], cfg: &mut ConvertConfig, encoder: &mut CommandEncoder, device: &Device, width: u32, height: u32, scale: Vec2) -> Result<(), DrawError> {
+        self.configure(cfg);
+        self.calls.clear();
+        self.lbf = None;
+        self.prj = uis.iter().map(|ui| (ui.1, ui.2)).collect();
+        self.scl = scale;
+        self.tsz = (width, height);
+        self.sts = DrawStats::default();
+        self.cst = ConvertStatus::default();
+
+        // A minimized window reports a zero size, which would put NaNs into the projection; there is nothing to see anyway.
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        // Every UI is appended to the same geometry buffers and addressed through its own uniform slot.
+        let (mut vnd, mut ind) = (0, 0);
+        let full = Viewport { x: 0, y: 0, width, height };
+        for (slot, ui) in uis.iter_mut().enumerate() {
+            let (vn, en) = self.convert_ui(ui.0, cfg, device, width, height, scale, ui.1.unwrap_or(full), slot as u32 * LOCALS_STRIDE, vnd, ind)?;
+            vnd += vn;
+            ind += en;
+        }
+
+        // Binding an empty vertex or index buffer is rejected by some backends, so geometry-less frames record no draws.
+        if vnd == 0 || ind == 0 {
+            self.calls.clear();
+        }
+        if self.vld && self.calls.is_empty() {
+            warn!("frame produced no draw calls ({} vertex / {} index bytes converted); was the UI built before drawing?", vnd, ind);
+        }
+
+        let indices = self.calls.iter().map(|call| (call.elements.end - call.elements.start) as usize).sum();
+        self.sts = DrawStats {
+            draw_calls: self.calls.len(),
+            vertices: vnd / self.vst,
+            indices,
+            triangles: indices / 3,
+        };
+
+        if !self.calls.is_empty() {
+            let projs = self.projections(width, height);
+            self.upload_locals(device, encoder, &projs);
+            upload_buffer(device, encoder, &self.vbd[..align4(vnd).min(self.vsz)], &self.vbf);
+            upload_buffer(device, encoder, &self.ebd[..align4(ind).min(self.esz)], &self.ebf);
+
+            // A line list takes two indices per segment, so the triangle indices can't be reused as they are.
+            if self.wfm && self.wpo.is_some() {
+                let triangles: Vec<u16> = self.ebd[..ind].chunks_exact(2).map(|b| u16::from_ne_bytes([b[0], b[1]])).collect();
+                self.lbf = Some(device.create_buffer_with_data(as_typed_slice(&triangle_edges(&triangles)), BufferUsage::INDEX));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the projection, tint and gamma for [`Drawer::draw_prepared`], which doesn't go through `prepare`.
+    /// Parameters are as for [`Drawer::prepare`].
+    pub fn prepare_uniforms(&mut self, encoder: &mut CommandEncoder, device: &Device, width: u32, height: u32, scale: Vec2, projection: Option<[[f32; 4]; 4]>) {
+        self.prj = vec![(self.vpt, projection)];
+        self.scl = scale;
+        self.tsz = (width, height);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let projs = self.projections(width, height);
+        self.upload_locals(device, encoder, &projs);
+    }
+
+    // Projections of the last prepared UIs on a `width`×`height` target.
+    fn projections(&self, width: u32, height: u32) -> Vec<Ortho> {
+        let full = Viewport { x: 0, y: 0, width, height };
+        self.prj.iter().map(|&(viewport, proj)| proj.unwrap_or_else(|| ortho(viewport.unwrap_or(full), width, height, self.scl))).collect()
+    }
+
+    // Writes one `Locals` slot per projection, growing the uniform buffer as needed.
+    fn upload_locals(&mut self, device: &Device, encoder: &mut CommandEncoder, projs: &[Ortho]) {
+        if projs.len() > self.uls {
+            self.uls = projs.len().next_power_of_two();
+            let label = sublabel(self.lbl.as_deref(), "uniforms");
+            self.ubf = create_uniform_buffer(device, self.uls, label.as_deref());
+            self.ubg = create_uniform_group(device, &self.ula, &self.ubf, label.as_deref());
+            self.ulc.clear();
+        }
+
+        let mut locals = vec![0; projs.len() * LOCALS_STRIDE as usize];
+        for (dst, &proj) in locals.chunks_mut(LOCALS_STRIDE as usize).zip(projs) {
+            let src = [Locals {
+                proj,
+                tint: self.tnt,
+                gamma: self.gmm,
+                y_scale: if self.fly { -1.0 } else { 1.0 },
+                _pad: [0.0; 2],
+            }];
+            dst[..size_of::<Locals>()].copy_from_slice(as_typed_slice(&src));
+        }
+
+        // Projections, tint and gamma rarely change between frames, so the copy is skipped while they match the last upload.
+        //TODO: stage through queue.write_buffer once wgpu is bumped past 0.5.
+        if locals != self.ulc {
+            upload_buffer(device, encoder, &locals, &self.ubf);
+            self.ulc = locals;
+        }
+    }
+
+    // Converts one UI into the geometry buffers after the `voff`/`eoff` bytes used by earlier UIs and appends its draw
+    // calls, returning the vertex and index bytes it added.
+    fn convert_ui(&mut self, ctx: &mut Context, cfg: &mut ConvertConfig, device: &Device, width: u32, height: u32, scale: Vec2, viewport: Viewport, locals: u32, voff: usize, eoff: usize) -> Result<(usize, usize), DrawError> {
+        self.clear_commands();
+
+        // Nuklear silently truncates geometry that doesn't fit the fixed buffers, so grow them and convert again.
+        let mut status = ConvertStatus::default();
+        let (vnd, ind) = loop {
+            self.vbd.resize(self.vsz, 0);
+            self.ebd.resize(self.esz, 0);
+
+            let (vnd, ind) = {
+                let mut vbuf = NkBuffer::with_fixed(&mut self.vbd[voff..]);
+                let mut ebuf = NkBuffer::with_fixed(&mut self.ebd[eoff..]);
+
+                ctx.convert(&mut self.cmd, &mut vbuf, &mut ebuf, cfg);
+
+                (vbuf.info().2, ebuf.info().2)
+            };
+
+            if voff + vnd <= self.vsz && eoff + ind <= self.esz {
+                break (vnd, ind);
+            }
+            if voff + vnd > self.vsz {
+                self.vsz = (voff + vnd).max(self.vsz * 2);
+                status.vertex_overflow = true;
+            }
+            if eoff + ind > self.esz {
+                self.esz = (eoff + ind).max(self.esz * 2);
+                status.element_overflow = true;
+            }
+            clear_buffer(&mut self.cmd);
+        };
+
+        if status != ConvertStatus::default() {
+            warn!("nuklear geometry overflowed ({:?}), grew buffers to {} vertex / {} index bytes", status, self.vsz, self.esz);
+            self.cst.vertex_overflow |= status.vertex_overflow;
+            self.cst.element_overflow |= status.element_overflow;
+            self.vbf = create_geometry_buffer(device, self.vsz, BufferUsage::VERTEX, sublabel(self.lbl.as_deref(), "vertices").as_deref());
+            self.ebf = create_geometry_buffer(device, self.esz, BufferUsage::INDEX, sublabel(self.lbl.as_deref(), "indices").as_deref());
+        }
+
+        let base_vertex = (voff / self.vst) as i32;
+        let mut start = (eoff / size_of::<u16>()) as u32;
+
+        for (index, cmd) in ctx.draw_command_iterator(&self.cmd).into_iter().enumerate() {
+            if cmd.elem_count() < 1 {
+                continue;
+            }
+
+            // Command handles are untyped, so id() normally yields a value and a pointer handle simply won't match a slot.
+            // Should a binding ever report no id, draw the command untextured rather than failing the frame.
+            let id = cmd.texture().id().unwrap_or(0);
+            if self.find_res(id).is_none() {
+                if self.vld {
+                    let clip = cmd.clip_rect();
+                    warn!("draw command {} ({} indices, clip {}x{} at {},{}) uses unregistered texture id {}", index, cmd.elem_count(), clip.w, clip.h, clip.x, clip.y, id);
+                }
+                self.calls.clear();
+                return Err(DrawError::MissingTexture(id));
+            }
+
+            let end = start + cmd.elem_count();
+            let scissor = match self.command_scissor(cmd.clip_rect(), scale, viewport, width, height) {
+                Some(scissor) => scissor,
+                None => {
+                    if self.vld {
+                        let clip = cmd.clip_rect();
+                        warn!("draw command {} skipped: clip {}x{} at {},{} is empty at this scale or outside the render region", index, clip.w, clip.h, clip.x, clip.y);
+                    }
+                    start = end;
+                    continue;
+                }
+            };
+
+            // Picking needs every command in its own call to tell them apart.
+            let pick_id = self.calls.len() as u32 + 1;
+            let call = DrawCall {
+                texture: id,
+                scissor,
+                locals,
+                base_vertex,
+                elements: start..end,
+                pick_id,
+            };
+            push_call(&mut self.calls, call, !self.pik);
+
+            start = end;
+        }
+
+        Ok((vnd, ind))
+    }
+
+    // Pixel scissor of a command, or None when nothing of it can show: it lies outside the render region or its clip
+    // rect rounds to zero pixels, as sub-pixel rects do on tiny targets. Some backends reject empty scissors outright.
+    fn command_scissor(&self, clip: &nuklear::Rect, scale: Vec2, viewport: Viewport, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        let scissor = scissor_rect(clip, scale, viewport, width, height);
+        if scissor.2 == 0 || scissor.3 == 0 {
+            return None;
+        }
+        // Unflipped geometry is mirrored vertically, so its clip rects must be too.
+        let scissor = if self.fly { scissor } else { (scissor.0, height - scissor.1 - scissor.3, scissor.2, scissor.3) };
+        match self.rgn {
+            Some(region) => intersect_rect(scissor, region),
+            None => Some(scissor),
+        }
+    }
+
+    /// Records the draw calls from the last [`Drawer::prepare`] into a pass the caller manages, e.g. one shared with
+    /// other overlays. The pass must target [`Drawer::target_format`] with [`Drawer::sample_count`] samples.
+    pub fn draw_into<'a>(&'a self, rpass: &mut RenderPass<'a>) {
+        // The caller may have set a scissor of its own, so the first call always sets one.
+        self.record_calls(rpass, None);
+    }
+
+    // Records the prepared calls, given the scissor `rpass` is known to have.
+    fn record_calls<'a>(&'a self, rpass: &mut RenderPass<'a>, mut current: Option<(u32, u32, u32, u32)>) {
+        if self.calls.is_empty() {
+            return;
+        }
+
+        // Edge lists hold two indices for every triangle index, so call ranges double.
+        let lines = self.lbf.as_ref();
+        self.bind_pipeline(rpass, &self.vbf, lines.unwrap_or(&self.ebf), lines.is_some());
+
+        for call in &self.calls {
+            let elements = if lines.is_some() { call.elements.start * 2..call.elements.end * 2 } else { call.elements.clone() };
+            // Textures can only be removed through &mut self, so every id checked in prepare is still live.
+            if let Some(res) = self.find_res(call.texture) {
+                self.record_call(rpass, res, call.locals, &mut current, call.scissor, elements, call.base_vertex, call.pick_id);
+            }
+        }
+    }
+
+    /// Draws geometry the caller converted with `ctx.convert` into its own buffers, skipping the drawer's conversion and
+    /// uploads. `commands` are that conversion's draw commands, e.g. from `ctx.draw_command_iterator`; the buffers must
+    /// use the layout [`Drawer::configure`] sets, with `u16` indices. Upload the frame's uniforms with
+    /// [`Drawer::prepare_uniforms`] first. Every texture is checked before anything is recorded.
+    pub fn draw_prepared<'a, 'c, I>(&'a self, rpass: &mut RenderPass<'a>, vertex_buf: &'a Buffer, index_buf: &'a Buffer, commands: I, width: u32, height: u32, scale: Vec2) -> Result<(), DrawError>
+    where
+        I: IntoIterator<Item = &'c DrawCommand>,
+    {
+        let viewport = self.vpt.unwrap_or(Viewport { x: 0, y: 0, width, height });
+        let mut calls = Vec::new();
+        let mut start = 0;
+        for cmd in commands {
+            if cmd.elem_count() < 1 {
+                continue;
+            }
+            let id = cmd.texture().id().unwrap_or(0);
+            let res = self.find_res(id).ok_or(DrawError::MissingTexture(id))?;
+            let end = start + cmd.elem_count();
+            if let Some(scissor) = self.command_scissor(cmd.clip_rect(), scale, viewport, width, height) {
+                calls.push((res, scissor, start..end));
+            }
+            start = end;
+        }
+
+        if calls.is_empty() {
+            return Ok(());
+        }
+
+        self.bind_pipeline(rpass, vertex_buf, index_buf, false);
+        let mut current = None;
+        for (i, (res, scissor, elements)) in calls.into_iter().enumerate() {
+            self.record_call(rpass, res, 0, &mut current, scissor, elements, 0, i as u32 + 1);
+        }
+        Ok(())
+    }
+
+    fn bind_pipeline<'a>(&'a self, rpass: &mut RenderPass<'a>, vertex_buf: &'a Buffer, index_buf: &'a Buffer, lines: bool) {
+        match self.wpo {
+            Some(ref wpo) if lines => rpass.set_pipeline(wpo),
+            _ => rpass.set_pipeline(&self.pso),
+        }
+
+        rpass.set_vertex_buffer(0, vertex_buf, 0, 0);
+        rpass.set_index_buffer(index_buf, 0, 0);
+    }
+
+    // Records one indexed draw, setting the scissor unless `current` already matches it.
+    fn record_call<'a>(&'a self, rpass: &mut RenderPass<'a>, res: &'a WgpuTexture, locals: u32, current: &mut Option<(u32, u32, u32, u32)>, scissor: (u32, u32, u32, u32), elements: Range<u32>, base_vertex: i32, pick_id: u32) {
+        rpass.set_bind_group(0, &self.ubg, &[locals]);
+        rpass.set_bind_group(1, &res.bind_group, &[]);
+
+        if *current != Some(scissor) {
+            *current = Some(scissor);
+            let (x, y, w, h) = scissor;
+            rpass.set_scissor_rect(x, y, w, h);
+        }
+
+        // The vertex shader forwards the instance index as the pick id. Other drawers keep instance 0, so they
+        // don't depend on first-instance support.
+        let instances = if self.pik { pick_id..pick_id + 1 } else { 0..1 };
+        rpass.draw_indexed(elements, base_vertex, instances);
+    }
+
+    /// Records and submits a whole frame into `view`, for the common case of a single-sampled target without depth.
+    pub fn render_frame(&mut self, ctx: &mut Context, cfg: &mut ConvertConfig, device: &Device, queue: &Queue, view: &TextureView, width: u32, height: u32, scale: Vec2) -> Result<(), DrawError> {
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        self.draw(ctx, cfg, &mut encoder, view, None, None, device, width, height, scale, None, None)?;
+        queue.submit(&[encoder.finish()]);
+        Ok(())
+    }
+
+    /// Renders into an offscreen `target` and returns the view used as the attachment.
+    /// `target` must have been created with `TextureUsage::OUTPUT_ATTACHMENT` and in [`Drawer::target_format`].
+    pub fn draw_to_texture(&mut self, ctx: &mut Context, cfg: &mut ConvertConfig, encoder: &mut CommandEncoder, target: &Texture, device: &Device, width: u32, height: u32, scale: Vec2, clear: Option<Color>) -> Result<TextureView, DrawError> {
+        let view = target.create_default_view();
+        self.draw(ctx, cfg, encoder, &view, None, None, device, width, height, scale, clear, None)?;
+        Ok(view)
+    }
+
+    /// Blocks until the GPU has finished all submitted work, e.g. before mapping a buffer a headless renderer copied the
+    /// frame into. [`Drawer::capture`] already does this itself.
+    pub fn poll_blocking(&self, device: &Device) {
+        device.poll(Maintain::Wait);
+    }
+
+    /// Renders the UI into an offscreen texture and reads it back as tightly packed rows, RGBA8 for 8-bit color targets
+    /// and raw half floats for `Rgba16Float`, e.g. for screenshots or image-based tests. Blocks until the GPU has
+    /// finished. Clears to transparent unless a clear color is set, so the result never contains stale memory.
+    pub fn capture(&mut self, ctx: &mut Context, cfg: &mut ConvertConfig, device: &Device, queue: &Queue, width: u32, height: u32, scale: Vec2) -> Result<Vec<u8>, DrawError> {
+        let (format, sample_count) = (self.fmt, self.msc);
+        let size = Extent3d { width, height, depth: 1 };
+        let target = |sample_count, usage| {
+            device.create_texture(&TextureDescriptor {
+                label: None,
+                size,
+                array_layer_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                mip_level_count: 1,
+                sample_count,
+                usage,
+            })
+        };
+
+        let texture = target(1, TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::COPY_SRC);
+        let view = texture.create_default_view();
+        let clear = Some(self.clear_color().unwrap_or(Color::TRANSPARENT));
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        if sample_count > 1 {
+            let msaa = target(sample_count, TextureUsage::OUTPUT_ATTACHMENT).create_default_view();
+            self.draw(ctx, cfg, &mut encoder, &msaa, Some(&view), None, device, width, height, scale, clear, None)?;
+        } else {
+            self.draw(ctx, cfg, &mut encoder, &view, None, None, device, width, height, scale, clear, None)?;
+        }
+
+        let row = width * bytes_per_pixel(format);
+        let pitch = pad_to_256(row);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: pitch as u64 * height as u64,
+            usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+        });
+        encoder.copy_texture_to_buffer(
+            TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: Origin3d::ZERO,
+            },
+            BufferCopyView {
+                buffer: &buffer,
+                offset: 0,
+                bytes_per_row: pitch,
+                rows_per_image: height,
+            },
+            size,
+        );
+        queue.submit(&[encoder.finish()]);
+
+        let mapping = wait(device, buffer.map_read(0, pitch as u64 * height as u64)).map_err(|_| DrawError::ReadbackFailed)?;
+        let mut pixels = unpad_rows(mapping.as_slice(), row as usize, pitch as usize);
+
+        if let TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb = format {
+            for px in pixels.chunks_mut(4) {
+                px.swap(0, 2);
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Overwrites the `w`×`h` region at (`x`, `y`) of an existing texture with tightly packed pixels in its format.
+    /// Only the base mip level is updated.
+    pub fn update_texture_region(&mut self, device: &Device, queue: &Queue, mut handle: Handle, x: u32, y: u32, w: u32, h: u32, data: &[u8]) -> Result<(), TextureError> {
+        // Id 0 is the built-in white texture, which no handle given out refers to.
+        let id = handle.id().unwrap_or(-1);
+        if id <= 0 {
+            return Err(TextureError::MissingTexture(id));
+        }
+        let res = self.find_res(id).ok_or(TextureError::MissingTexture(id))?;
+
+        if res.texture.is_none() {
+            return Err(TextureError::NotWritable(id));
+        }
+
+        let expected = w as usize * h as usize * bytes_per_pixel(res.format) as usize;
+        if data.len() != expected {
+            return Err(TextureError::DataSize { expected, actual: data.len() });
+        }
+        // Subtracting from the size rather than adding to the offset keeps huge regions from overflowing.
+        if x > res.width || w > res.width - x || y > res.height || h > res.height - y {
+            return Err(TextureError::OutOfBounds);
+        }
+
+        res.write(device, queue, 0, x, y, w, h, data);
+        Ok(())
+    }
+
+    /// Releases every texture and then the pipeline and buffers. Call this before dropping the `Device` the drawer was created with.
+    pub fn destroy(mut self) {
+        self.tex.clear();
+    }
+
+    fn find_res(&self, id: i32) -> Option<&WgpuTexture> {
+        if id == 0 {
+            Some(&self.white)
+        } else if id > 0 && id as usize <= self.tex.len() {
+            self.tex.get((id - 1) as usize).and_then(Option::as_ref)
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Drawer {
+    fn drop(&mut self) {
+        let live = self.texture_count();
+        if live > 0 {
+            warn!("Drawer dropped with {} live texture(s); use Drawer::destroy for deterministic teardown", live);
+        }
+    }
+}
+
+/// Where [`FontAtlasBuilder::build`] placed one image, in pixels and as normalized texture coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+/// Packs glyphs, icons and other small BGRA8 images into a single texture, so everything drawn from it shares one
+/// bind group and consecutive commands merge into one draw call.
+///
+/// Images are placed on shelves no wider than `max_width` (or the widest image), tallest first, with `padding` empty
+/// pixels between them so linear filtering doesn't bleed neighbours into each other.
+#[derive(Debug, Clone)]
+pub struct FontAtlasBuilder {
+    images: Vec<(Vec<u8>, u32, u32)>,
+    max_width: u32,
+    padding: u32,
+    sampler: SamplerConfig,
+}
+
+impl FontAtlasBuilder {
+    pub fn new(max_width: u32) -> Self {
+        FontAtlasBuilder {
+            images: Vec::new(),
+            max_width,
+            padding: 1,
+            sampler: SamplerConfig::default(),
+        }
+    }
+
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn sampler(mut self, sampler: SamplerConfig) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Queues a tightly packed BGRA8 image and returns its index into the regions returned by `build`.
+    pub fn add(&mut self, image: &[u8], width: u32, height: u32) -> Result<usize, TextureError> {
+        let expected = width as usize * height as usize * 4;
+        if image.len() != expected {
+            return Err(TextureError::DataSize { expected, actual: image.len() });
+        }
+        self.images.push((image.to_vec(), width, height));
+        Ok(self.images.len() - 1)
+    }
+
+    /// Packs the queued images, uploads the atlas through [`Drawer::add_texture_with_sampler`] and returns its handle
+    /// along with one region per image, in the order they were added.
+    pub fn build(self, drawer: &mut Drawer, device: &Device, queue: &Queue) -> (Handle, Vec<AtlasRegion>) {
+        let sizes: Vec<_> = self.images.iter().map(|&(_, w, h)| (w, h)).collect();
+        let (width, height, origins) = pack_shelves(&sizes, self.max_width, self.padding);
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        for (&(ref image, w, h), &(x, y)) in self.images.iter().zip(&origins) {
+            let row = w as usize * 4;
+            for (line, src) in image.chunks(row).take(h as usize).enumerate() {
+                let start = ((y as usize + line) * width as usize + x as usize) * 4;
+                pixels[start..start + row].copy_from_slice(src);
+            }
+        }
+
+        let handle = drawer.add_texture_with_sampler(device, queue, &pixels, width, height, self.sampler);
+        let (aw, ah) = (width as f32, height as f32);
+        let regions = sizes
+            .iter()
+            .zip(&origins)
+            .map(|(&(w, h), &(x, y))| AtlasRegion {
+                x,
+                y,
+                width: w,
+                height: h,
+                uv_min: [x as f32 / aw, y as f32 / ah],
+                uv_max: [(x + w) as f32 / aw, (y + h) as f32 / ah],
+            })
+            .collect();
+        (handle, regions)
+    }
+}
+
+// Shelf packer: images go left to right in order of decreasing height, starting a new shelf below the tallest image
+// of the current one when a row is full. Returns the atlas size and each image's top-left corner, in input order.
+fn pack_shelves(sizes: &[(u32, u32)], max_width: u32, padding: u32) -> (u32, u32, Vec<(u32, u32)>) {
+    let width = sizes.iter().map(|&(w, _)| w).fold(max_width.max(1), u32::max);
+
+    let mut order: Vec<_> = (0..sizes.len()).collect();
+    order.sort_by(|&a, &b| sizes[b].1.cmp(&sizes[a].1));
+
+    let mut origins = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf) = (0, 0, 0);
+    for i in order {
+        let (w, h) = sizes[i];
+        if x > 0 && x + w > width {
+            x = 0;
+            y += shelf + padding;
+            shelf = 0;
+        }
+        origins[i] = (x, y);
+        x += w + padding;
+        shelf = shelf.max(h);
+    }
+
+    (width, (y + shelf).max(1), origins)
+}
+
+// Logical UI coordinates to clip space, with logical (0, 0) at the top-left corner of `viewport` once the vertex shader
+// has flipped Y.
+fn ortho(viewport: Viewport, width: u32, height: u32, scale: Vec2) -> Ortho {
+    let (w, h) = (width as f32, height as f32);
+    [
+        [2.0f32 * scale.x / w, 0.0f32, 0.0f32, 0.0f32],
+        [0.0f32, 2.0f32 * scale.y / h, 0.0f32, 0.0f32],
+        [0.0f32, 0.0f32, -1.0f32, 0.0f32],
+        [2.0f32 * viewport.x as f32 / w - 1.0, 2.0f32 * viewport.y as f32 / h - 1.0, 0.0f32, 1.0f32],
+    ]
+}
+
+// Scales a nuklear clip rect, offsets it into `viewport` and clamps it to both the viewport and the target, since wgpu
+// rejects scissors that leave the attachment.
+fn scissor_rect(clip: &nuklear::Rect, scale: Vec2, viewport: Viewport, width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let (left, top) = (viewport.x as f32, viewport.y as f32);
+    let right = (viewport.x + viewport.width).min(width) as f32;
+    let bottom = (viewport.y + viewport.height).min(height) as f32;
+    let x0 = (left + clip.x * scale.x).max(left).min(right);
+    let y0 = (top + clip.y * scale.y).max(top).min(bottom);
+    let x1 = (left + (clip.x + clip.w) * scale.x).max(x0).min(right);
+    let y1 = (top + (clip.y + clip.h) * scale.y).max(y0).min(bottom);
+    (x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32)
+}
+
+fn intersect_rect(rect: (u32, u32, u32, u32), region: Viewport) -> Option<(u32, u32, u32, u32)> {
+    let (x0, y0) = (rect.0.max(region.x), rect.1.max(region.y));
+    let x1 = (rect.0 + rect.2).min(region.x + region.width);
+    let y1 = (rect.1 + rect.3).min(region.y + region.height);
+    if x1 > x0 && y1 > y0 {
+        Some((x0, y0, x1 - x0, y1 - y0))
+    } else {
+        None
+    }
+}
+
+// Appends `call`, or with `merge` extends the previous call instead when it continues it with the same state: runs of
+// glyphs share a texture and clip rect, so this saves rebinding for most of a UI. Index ranges are relative to their
+// UI's vertex region, so calls over different regions never merge, and neither do calls around a skipped command,
+// whose indices lie between them.
+fn push_call(calls: &mut Vec<DrawCall>, call: DrawCall, merge: bool) {
+    match calls.last_mut() {
+        Some(last) if merge && last.texture == call.texture && last.scissor == call.scissor && last.locals == call.locals && last.base_vertex == call.base_vertex && last.elements.end == call.elements.start => last.elements.end = call.elements.end,
+        _ => calls.push(call),
+    }
+}
+
+// Line list indices for the edges of each triangle in a triangle list, in the same order.
+fn triangle_edges(indices: &[u16]) -> Vec<u16> {
+    let mut lines = Vec::with_capacity(indices.len() * 2);
+    for t in indices.chunks_exact(3) {
+        lines.extend_from_slice(&[t[0], t[1], t[1], t[2], t[2], t[0]]);
+    }
+    lines
+}
+
+// wgpu 0.5 completes buffer mappings from inside device.poll, so a waker that does nothing is enough to drive them.
+fn wait<F: Future>(device: &Device, future: F) -> F::Output {
+    let waker = noop_waker();
+    let mut cx = std::task::Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        device.poll(Maintain::Wait);
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn noop_waker() -> Waker {
+    fn clone(data: *const ()) -> RawWaker {
+        RawWaker::new(data, &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    unsafe { Waker::from_raw(RawWaker::new(null(), &VTABLE)) }
+}
+
+fn clear_buffer(buf: &mut NkBuffer) {
+    unsafe { nuklear::nuklear_sys::nk_buffer_clear(buf.as_mut()) }
+}
+
+// Scales the color channels of 4-byte pixels by their alpha, rounding to nearest. Alpha is last in BGRA and RGBA alike.
+fn premultiply(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        let a = px[3] as u32;
+        for c in &mut px[..3] {
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+// Levels in a full chain down to 1×1: floor(log2(max(width, height))) + 1.
+fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+// Halves an image with a 2×2 box filter, clamping at odd edges.
+fn downsample(src: &[u8], width: u32, height: u32, bpp: usize) -> Vec<u8> {
+    let (w, h) = (width as usize, height as usize);
+    let (dw, dh) = ((w / 2).max(1), (h / 2).max(1));
+    let mut dst = vec![0; dw * dh * bpp];
+    for y in 0..dh {
+        let (y0, y1) = (2 * y, (2 * y + 1).min(h - 1));
+        for x in 0..dw {
+            let (x0, x1) = (2 * x, (2 * x + 1).min(w - 1));
+            for c in 0..bpp {
+                let sum: u32 = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)].iter().map(|&(sx, sy)| src[(sy * w + sx) * bpp + c] as u32).sum();
+                dst[(y * dw + x) * bpp + c] = ((sum + 2) / 4) as u8;
+            }
+        }
+    }
+    dst
+}
+
+fn bytes_per_pixel(format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::R8Unorm => 1,
+        TextureFormat::Rgba16Float => 8,
+        _ => 4,
+    }
+}
+
+// An attribute running past the stride would make nuklear write into the next vertex, and wgpu rejects the rest.
+fn check_vertex_layout(stride: usize, nuklear: &[(DrawVertexLayoutAttribute, DrawVertexLayoutFormat, Size)], attributes: &[VertexAttributeDescriptor]) -> Result<(), DrawerError> {
+    let fits = stride > 0
+        && align4(stride) == stride
+        && nuklear.iter().all(|&(attr, format, offset)| attr == DrawVertexLayoutAttribute::AttributeCount || offset + nk_format_size(format) <= stride)
+        && attributes.iter().all(|attr| attr.offset + vertex_format_size(attr.format) <= stride as u64);
+    if fits {
+        Ok(())
+    } else {
+        Err(DrawerError::VertexLayoutMismatch { stride })
+    }
+}
+
+// Empty buffers are invalid in wgpu, and anything below one triangle can never be drawn from.
+fn check_buffer_sizes(stride: usize, vbo_size: usize, ebo_size: usize) -> Result<(), DrawerError> {
+    for &(usage, size, minimum) in &[(BufferUsage::VERTEX, vbo_size, 3 * stride), (BufferUsage::INDEX, ebo_size, align4(3 * size_of::<u16>()))] {
+        if size < minimum {
+            return Err(DrawerError::BufferTooSmall { usage, size, minimum });
+        }
+    }
+    Ok(())
+}
+
+// Bytes nuklear writes for one attribute: positions and UVs take two components of a scalar format, colors one packed value.
+fn nk_format_size(format: DrawVertexLayoutFormat) -> usize {
+    match format {
+        DrawVertexLayoutFormat::Char | DrawVertexLayoutFormat::Uchar => 2,
+        DrawVertexLayoutFormat::Short | DrawVertexLayoutFormat::Ushort => 4,
+        DrawVertexLayoutFormat::Int | DrawVertexLayoutFormat::Uint | DrawVertexLayoutFormat::Float => 8,
+        DrawVertexLayoutFormat::Double => 16,
+        DrawVertexLayoutFormat::R8G8B8 => 3,
+        DrawVertexLayoutFormat::R16G16B16 => 6,
+        DrawVertexLayoutFormat::R32G32B32 => 12,
+        DrawVertexLayoutFormat::R8G8B8A8 | DrawVertexLayoutFormat::B8G8R8A8 | DrawVertexLayoutFormat::Rgb32 | DrawVertexLayoutFormat::Rgba32 => 4,
+        DrawVertexLayoutFormat::R16G15B16A16 => 8,
+        DrawVertexLayoutFormat::R32G32B32A32 | DrawVertexLayoutFormat::R32G32B32A32Float => 16,
+        DrawVertexLayoutFormat::R32G32B32A32Double => 32,
+        DrawVertexLayoutFormat::Count => 0,
+    }
+}
+
+fn vertex_format_size(format: VertexFormat) -> u64 {
+    match format {
+        VertexFormat::Uchar2 | VertexFormat::Char2 | VertexFormat::Uchar2Norm | VertexFormat::Char2Norm => 2,
+        VertexFormat::Uchar4 | VertexFormat::Char4 | VertexFormat::Uchar4Norm | VertexFormat::Char4Norm => 4,
+        VertexFormat::Ushort2 | VertexFormat::Short2 | VertexFormat::Ushort2Norm | VertexFormat::Short2Norm | VertexFormat::Half2 => 4,
+        VertexFormat::Ushort4 | VertexFormat::Short4 | VertexFormat::Ushort4Norm | VertexFormat::Short4Norm | VertexFormat::Half4 => 8,
+        VertexFormat::Float | VertexFormat::Uint | VertexFormat::Int => 4,
+        VertexFormat::Float2 | VertexFormat::Uint2 | VertexFormat::Int2 => 8,
+        VertexFormat::Float3 | VertexFormat::Uint3 | VertexFormat::Int3 => 12,
+        VertexFormat::Float4 | VertexFormat::Uint4 | VertexFormat::Int4 => 16,
+    }
+}
+
+fn srgb_format(format: TextureFormat) -> TextureFormat {
+    match format {
+        TextureFormat::Bgra8Unorm => TextureFormat::Bgra8UnormSrgb,
+        TextureFormat::Rgba8Unorm => TextureFormat::Rgba8UnormSrgb,
+        f => f,
+    }
+}
+
+// Returns the sampler for `config`, creating it on first use. Drawers see only a handful of distinct configs, so a
+// linear scan beats hashing the float fields.
+fn shared_sampler<'a>(samplers: &'a mut Vec<(SamplerConfig, Sampler)>, device: &Device, config: &SamplerConfig) -> &'a (SamplerConfig, Sampler) {
+    let index = match samplers.iter().position(|(c, _)| c == config) {
+        Some(index) => index,
+        None => {
+            let sampler = device.create_sampler(&SamplerDescriptor {
+                address_mode_u: config.address_mode_u,
+                address_mode_v: config.address_mode_v,
+                address_mode_w: AddressMode::ClampToEdge,
+                mag_filter: config.mag_filter,
+                min_filter: config.min_filter,
+                mipmap_filter: config.mipmap_filter,
+                lod_min_clamp: config.lod_min_clamp,
+                lod_max_clamp: config.lod_max_clamp,
+                compare: CompareFunction::Always,
+            });
+            samplers.push((*config, sampler));
+            samplers.len() - 1
+        }
+    };
+    &samplers[index]
+}
+
+fn create_texture_group(device: &Device, layout: &BindGroupLayout, view: &TextureView, sampler: &Sampler, locals: &Buffer, label: Option<&str>) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label,
+        layout,
+        bindings: &[
+            Binding {
+                binding: 0,
+                resource: BindingResource::TextureView(view),
+            },
+            Binding {
+                binding: 1,
+                resource: BindingResource::Sampler(sampler),
+            },
+            Binding {
+                binding: 2,
+                resource: BindingResource::Buffer {
+                    buffer: locals,
+                    range: 0..size_of::<[u32; 4]>() as u64,
+                },
+            },
+        ],
+    })
+}
+
+// Same curve as `to_linear` in ps.fx.
+fn srgb_to_linear(c: f64) -> f64 {
+    if c < 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// "<drawer label> <what>", for the resources of a labeled drawer.
+fn sublabel(label: Option<&str>, what: &str) -> Option<String> {
+    label.map(|label| format!("{} {}", label, what))
+}
+
+// Holds `slots` `Locals` blocks at dynamic-offset alignment.
+fn create_uniform_buffer(device: &Device, slots: usize, label: Option<&str>) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label,
+        size: slots as u64 * LOCALS_STRIDE as u64,
+        usage: BufferUsage::UNIFORM | BufferUsage::COPY_DST,
+    })
+}
+
+fn create_uniform_group(device: &Device, layout: &BindGroupLayout, buffer: &Buffer, label: Option<&str>) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label,
+        layout,
+        bindings: &[Binding {
+            binding: 0,
+            resource: BindingResource::Buffer {
+                buffer,
+                range: 0..(size_of::<Locals>() as u64),
+            },
+        }],
+    })
+}
+
+fn create_geometry_buffer(device: &Device, size: usize, usage: BufferUsage, label: Option<&str>) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label,
+        size: size as u64,
+        usage: usage | BufferUsage::COPY_DST,
+    })
+}
+
+fn upload_buffer(device: &Device, encoder: &mut CommandEncoder, data: &[u8], dst: &Buffer) {
+    if data.is_empty() {
+        return;
+    }
+    let stg = device.create_buffer_with_data(data, BufferUsage::COPY_SRC);
+    encoder.copy_buffer_to_buffer(&stg, 0, dst, 0, data.len() as u64);
+}
+
+// Buffer rows in buffer/texture copies must start on 256-byte boundaries, so tightly packed rows are staged with padding.
+fn pad_to_256(row: u32) -> u32 {
+    (row + 255) & !255
+}
+
+// Copies tightly packed rows of `row` bytes into rows `pitch` bytes apart.
+fn pad_rows(data: &[u8], row: usize, pitch: usize) -> Vec<u8> {
+    let mut padded = vec![0; pitch * (data.len() / row)];
+    for (dst, src) in padded.chunks_mut(pitch).zip(data.chunks(row)) {
+        dst[..row].copy_from_slice(src);
+    }
+    padded
+}
+
+fn unpad_rows(data: &[u8], row: usize, pitch: usize) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(row * (data.len() / pitch));
+    for line in data.chunks(pitch) {
+        pixels.extend_from_slice(&line[..row]);
+    }
+    pixels
+}
+
+fn align4(size: usize) -> usize {
+    (size + 3) & !3
+}
+
+fn as_typed_slice<T>(data: &[T]) -> &[u8] {
+    unsafe { from_raw_parts(data.as_ptr() as *const u8, data.len() * size_of::<T>()) }
+}
+
+#[cfg(not(feature = "glsl-runtime"))]
+fn vertex_spirv() -> Result<Vec<u32>, DrawerError> {
+    Ok(load_spirv(VS_SPIRV))
+}
+
+#[cfg(not(feature = "glsl-runtime"))]
+fn fragment_spirv(srgb: bool, picking: bool) -> Result<Vec<u32>, DrawerError> {
+    Ok(load_spirv(match (srgb, picking) {
+        (false, false) => PS_SPIRV,
+        (true, false) => PS_SRGB_SPIRV,
+        (false, true) => PS_PICK_SPIRV,
+        (true, true) => PS_SRGB_PICK_SPIRV,
+    }))
+}
+
+#[cfg(not(feature = "glsl-runtime"))]
+fn load_spirv(spv: &[u8]) -> Vec<u32> {
+    // Only called on the modules build.rs compiled, so a failure is a build bug rather than bad input.
+    read_spirv(std::io::Cursor::new(spv)).expect("invalid SPIR-V emitted by build.rs")
+}
+
+#[cfg(feature = "glsl-runtime")]
+fn vertex_spirv() -> Result<Vec<u32>, DrawerError> {
+    compile_glsl(VS_GLSL, glsl_to_spirv::ShaderType::Vertex)
+}
+
+#[cfg(feature = "glsl-runtime")]
+fn fragment_spirv(srgb: bool, picking: bool) -> Result<Vec<u32>, DrawerError> {
+    // Same defines build.rs injects for the ps_*.spv variants.
+    let mut code = PS_GLSL.to_string();
+    if srgb {
+        code = code.replacen("#version 450", "#version 450\n#define SRGB", 1);
+    }
+    if picking {
+        code = code.replacen("#version 450", "#version 450\n#define PICKING", 1);
+    }
+    compile_glsl(&code, glsl_to_spirv::ShaderType::Fragment)
+}
+
+#[cfg(feature = "glsl-runtime")]
+fn compile_glsl(code: &str, ty: glsl_to_spirv::ShaderType) -> Result<Vec<u32>, DrawerError> {
+    // The sources ship with the crate, so a failure means glslang is missing or broken, or the sources were edited.
+    let spv = glsl_to_spirv::compile(code, ty).map_err(DrawerError::ShaderCompile)?;
+    read_spirv(spv).map_err(|e| DrawerError::ShaderCompile(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nuklear::{color_rgba, nk_string, nuklear_sys::nk_handle, Allocator, CommandBuffer, Flags, Image, PanelFlags, Rect, StyleItem, UserFont};
+
+    use std::{
+        os::raw::c_char,
+        sync::{Mutex, Once},
+    };
+
+    // Device-backed tests return early without a usable adapter, e.g. on CI runners without a GPU.
+    fn device() -> Option<(Device, Queue)> {
+        let options = RequestAdapterOptions {
+            power_preference: PowerPreference::LowPower,
+            compatible_surface: None,
+        };
+        let adapter = block_on(Adapter::request(&options, BackendBit::PRIMARY))?;
+        Some(block_on(adapter.request_device(&DeviceDescriptor::default())))
+    }
+
+    // Adapter and device requests resolve on their first poll in wgpu 0.5.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    // A font that measures every string as empty; enough for UIs without text.
+    fn null_font() -> UserFont {
+        unsafe extern "C" fn width(_: nk_handle, _: f32, _: *const c_char, _: i32) -> f32 {
+            0.0
+        }
+        let mut font = UserFont::default();
+        let raw: &mut nuklear::nuklear_sys::nk_user_font = font.as_mut();
+        raw.height = 13.0;
+        raw.width = Some(width);
+        font
+    }
+
+    static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct CaptureLog;
+
+    impl log::Log for CaptureLog {
+        fn enabled(&self, metadata: &log::LogMetadata) -> bool {
+            metadata.level() <= log::LogLevel::Warn
+        }
+
+        fn log(&self, record: &log::LogRecord) {
+            WARNINGS.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    // Warnings logged so far by any test, since the logger is global; installs it on first use.
+    fn warnings() -> Vec<String> {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            log::set_logger(|max| {
+                max.set(log::LogLevelFilter::Warn);
+                Box::new(CaptureLog)
+            })
+            .unwrap()
+        });
+        WARNINGS.lock().unwrap().clone()
+    }
+
+    // A context whose windows have neither padding nor background, so shapes painted on their canvas land exactly
+    // where they are drawn and nothing else is. `font` must outlive it.
+    fn context(font: &UserFont) -> (Context, ConvertConfig) {
+        let mut ctx = Context::new(&mut Allocator::new_vec(), font);
+        ctx.style_mut().window_mut().set_padding(Vec2 { x: 0.0, y: 0.0 });
+        ctx.style_mut().window_mut().set_fixed_background(StyleItem::color(color_rgba(0, 0, 0, 0)));
+        let mut cfg = ConvertConfig::default();
+        cfg.set_global_alpha(1.0);
+        (ctx, cfg)
+    }
+
+    // Starts a new frame with `shapes` painted on a window covering `width`×`height` logical units.
+    fn paint(ctx: &mut Context, width: f32, height: f32, shapes: impl FnOnce(&mut CommandBuffer)) {
+        ctx.clear();
+        ctx.begin(nk_string!("canvas"), Rect { x: 0.0, y: 0.0, w: width, h: height }, PanelFlags::NoScrollbar as Flags);
+        shapes(ctx.window_get_canvas_mut().unwrap());
+        ctx.end();
+    }
+
+    // Captures a 16×16 target covered by one rect of `color` and returns its center pixel.
+    fn capture_fill(drawer: &mut Drawer, device: &Device, queue: &Queue, color: nuklear::Color) -> Vec<u8> {
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        paint(&mut ctx, 16.0, 16.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, 0.0, color));
+        let pixels = drawer.capture(&mut ctx, &mut cfg, device, queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        let bpp = bytes_per_pixel(drawer.target_format()) as usize;
+        pixels[(8 * 16 + 8) * bpp..][..bpp].to_vec()
+    }
+
+    fn render_target(device: &Device, width: u32, height: u32, format: TextureFormat) -> Texture {
+        device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d { width, height, depth: 1 },
+            array_layer_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsage::OUTPUT_ATTACHMENT | TextureUsage::COPY_SRC,
+        })
+    }
+
+    // Reads back the base level of a texture as tightly packed rows, like `capture` but without rendering or swizzling.
+    fn read_texture(device: &Device, queue: &Queue, texture: &Texture, width: u32, height: u32, format: TextureFormat) -> Vec<u8> {
+        let row = width * bytes_per_pixel(format);
+        let pitch = pad_to_256(row);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: pitch as u64 * height as u64,
+            usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            TextureCopyView {
+                texture,
+                mip_level: 0,
+                array_layer: 0,
+                origin: Origin3d::ZERO,
+            },
+            BufferCopyView {
+                buffer: &buffer,
+                offset: 0,
+                bytes_per_row: pitch,
+                rows_per_image: height,
+            },
+            Extent3d { width, height, depth: 1 },
+        );
+        queue.submit(&[encoder.finish()]);
+        let mapping = wait(device, buffer.map_read(0, pitch as u64 * height as u64)).unwrap();
+        unpad_rows(mapping.as_slice(), row as usize, pitch as usize)
+    }
+
+    #[test]
+    fn vertex_layout_matches_vertex() {
+        assert_eq!(size_of::<Vertex>(), 20);
+
+        let v = Vertex::default();
+        let base = &v as *const Vertex as usize;
+        let fields = [v.pos.as_ptr() as usize, v.tex.as_ptr() as usize, v.col.as_ptr() as usize];
+        let sizes = [size_of::<[f32; 2]>(), size_of::<[f32; 2]>(), size_of::<[u8; 4]>()];
+        assert_eq!(vertex_layout().len(), fields.len());
+        for (location, (attr, (&field, &size))) in vertex_layout().iter().zip(fields.iter().zip(&sizes)).enumerate() {
+            assert_eq!(attr.offset as usize, field - base);
+            assert_eq!(vertex_format_size(attr.format) as usize, size);
+            assert_eq!(attr.shader_location as usize, location);
+        }
+
+        // Nuklear writes each attribute where the pipeline reads it.
+        for (&(_, format, offset), attr) in NK_VERTEX_LAYOUT.iter().zip(vertex_layout()) {
+            assert_eq!(offset as u64, attr.offset);
+            assert_eq!(nk_format_size(format) as u64, vertex_format_size(attr.format));
+        }
+    }
+
+    #[test]
+    fn larger_custom_vertex_fits() {
+        // The built-in vertex followed by a second UV set that only a custom shader reads.
+        let attributes = [
+            VertexAttributeDescriptor {
+                offset: 0,
+                format: VertexFormat::Float2,
+                shader_location: 0,
+            },
+            VertexAttributeDescriptor {
+                offset: 8,
+                format: VertexFormat::Float2,
+                shader_location: 1,
+            },
+            VertexAttributeDescriptor {
+                offset: 16,
+                format: VertexFormat::Uchar4Norm,
+                shader_location: 2,
+            },
+            VertexAttributeDescriptor {
+                offset: 20,
+                format: VertexFormat::Float2,
+                shader_location: 3,
+            },
+        ];
+        assert!(check_vertex_layout(28, &NK_VERTEX_LAYOUT, &attributes).is_ok());
+        assert!(check_vertex_layout(size_of::<Vertex>(), &NK_VERTEX_LAYOUT, vertex_layout()).is_ok());
+    }
+
+    #[test]
+    fn mismatched_stride_is_rejected() {
+        let mismatch = |stride, nuklear: &[_], attributes| match check_vertex_layout(stride, nuklear, attributes) {
+            Err(DrawerError::VertexLayoutMismatch { stride: s }) => s == stride,
+            _ => false,
+        };
+        assert!(mismatch(16, &NK_VERTEX_LAYOUT, vertex_layout()));
+        assert!(mismatch(0, &NK_VERTEX_LAYOUT, vertex_layout()));
+        assert!(mismatch(22, &NK_VERTEX_LAYOUT, vertex_layout()));
+
+        // The color starts inside the stride but its last bytes don't.
+        let mut overhang = NK_VERTEX_LAYOUT;
+        overhang[2].2 = 18;
+        assert!(mismatch(20, &overhang, &vertex_layout()[..2]));
+    }
+
+    #[test]
+    fn scissor_rect_stays_on_target() {
+        let full = Viewport { x: 0, y: 0, width: 100, height: 80 };
+        let one = Vec2 { x: 1.0, y: 1.0 };
+        let scissor = |x, y, w, h, scale| scissor_rect(&Rect { x, y, w, h }, scale, full, 100, 80);
+
+        assert_eq!(scissor(10.0, 20.0, 30.0, 40.0, one), (10, 20, 30, 40));
+        // Overhanging the bottom-right corner.
+        assert_eq!(scissor(90.0, 70.0, 50.0, 50.0, one), (90, 70, 10, 10));
+        // Negative coordinates would wrap to huge values if cast directly.
+        assert_eq!(scissor(-20.0, -10.0, 50.0, 40.0, one), (0, 0, 30, 30));
+        // Larger than the target on every side.
+        assert_eq!(scissor(-1000.0, -1000.0, 5000.0, 5000.0, one), (0, 0, 100, 80));
+        // Entirely off the target.
+        assert_eq!(scissor(200.0, 200.0, 10.0, 10.0, one), (100, 80, 0, 0));
+        assert_eq!(scissor(-50.0, -50.0, 10.0, 10.0, one), (0, 0, 0, 0));
+        // Scaled past the edge.
+        assert_eq!(scissor(40.0, 30.0, 20.0, 20.0, Vec2 { x: 2.0, y: 2.0 }), (80, 60, 20, 20));
+
+        for &(x, y, w, h) in &[(95.0, -5.0, 10.0, 100.0), (-3.0, 79.5, 200.0, 1.0), (f32::MAX, f32::MIN, f32::MAX, f32::MAX)] {
+            let (sx, sy, sw, sh) = scissor(x, y, w, h, one);
+            assert!(sx + sw <= 100 && sy + sh <= 80);
+        }
+    }
+
+    // Where the vertex shader puts logical point (x, y), in pixels from the top-left of a `width`×`height` target.
+    fn project(proj: Ortho, x: f32, y: f32, y_scale: f32, width: u32, height: u32) -> (f32, f32) {
+        let clip = |row: usize| proj[0][row] * x + proj[1][row] * y + proj[3][row];
+        let (ndc_x, ndc_y) = (clip(0) / clip(3), y_scale * clip(1) / clip(3));
+        ((ndc_x + 1.0) / 2.0 * width as f32, (1.0 - ndc_y) / 2.0 * height as f32)
+    }
+
+    fn assert_near(actual: (f32, f32), expected: (f32, f32)) {
+        assert!((actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn logical_units_scale_to_pixels() {
+        let full = |width, height| Viewport { x: 0, y: 0, width, height };
+
+        let proj = ortho(full(400, 400), 400, 400, Vec2 { x: 2.0, y: 2.0 });
+        assert_near(project(proj, 100.0, 100.0, -1.0, 400, 400), (200.0, 200.0));
+        assert_near(project(proj, 0.0, 0.0, -1.0, 400, 400), (0.0, 0.0));
+
+        let proj = ortho(full(300, 200), 300, 200, Vec2 { x: 1.5, y: 2.0 });
+        assert_near(project(proj, 100.0, 25.0, -1.0, 300, 200), (150.0, 50.0));
+        // Without the flip the origin is the bottom-left corner.
+        assert_near(project(proj, 100.0, 25.0, 1.0, 300, 200), (150.0, 150.0));
+        assert_near(project(proj, 0.0, 0.0, 1.0, 300, 200), (0.0, 200.0));
+    }
+
+    #[test]
+    fn custom_projection_translates() {
+        let (width, height) = (200, 100);
+        let one = Vec2 { x: 1.0, y: 1.0 };
+        let mut proj = ortho(Viewport { x: 0, y: 0, width, height }, width, height, one);
+        // What a caller would pass to `prepare` to move the UI 10 pixels right and 20 down.
+        proj[3][0] += 2.0 * 10.0 / width as f32;
+        proj[3][1] += 2.0 * 20.0 / height as f32;
+        assert_near(project(proj, 5.0, 5.0, -1.0, width, height), (15.0, 25.0));
+        assert_near(project(proj, 0.0, 0.0, -1.0, width, height), (10.0, 20.0));
+    }
+
+    #[test]
+    fn viewport_offsets_projection_and_scissors() {
+        let viewport = Viewport { x: 200, y: 100, width: 200, height: 200 };
+        let one = Vec2 { x: 1.0, y: 1.0 };
+        let proj = ortho(viewport, 400, 300, one);
+        assert_near(project(proj, 0.0, 0.0, -1.0, 400, 300), (200.0, 100.0));
+        assert_near(project(proj, 50.0, 20.0, -1.0, 400, 300), (250.0, 120.0));
+
+        // Clip rects are offset the same way and never leave the viewport.
+        assert_eq!(scissor_rect(&Rect { x: 10.0, y: 10.0, w: 20.0, h: 20.0 }, one, viewport, 400, 300), (210, 110, 20, 20));
+        assert_eq!(scissor_rect(&Rect { x: -50.0, y: -50.0, w: 1000.0, h: 1000.0 }, one, viewport, 400, 300), (200, 100, 200, 200));
+    }
+
+    #[test]
+    fn rows_pad_to_256_and_back() {
+        for &(width, pitch) in &[(1, 256), (64, 256), (65, 512)] {
+            let row = width * 4;
+            assert_eq!(pad_to_256(row), pitch);
+
+            let (row, pitch) = (row as usize, pitch as usize);
+            let tight: Vec<u8> = (0..row * 3).map(|i| i as u8).collect();
+            let padded = pad_rows(&tight, row, pitch);
+            assert_eq!(padded.len(), pitch * 3);
+            assert_eq!(&padded[pitch..pitch + row], &tight[row..2 * row]);
+            assert_eq!(unpad_rows(&padded, row, pitch), tight);
+        }
+    }
+
+    #[test]
+    fn shelves_dont_overlap() {
+        let sizes = [(10, 12), (30, 5), (7, 7), (64, 20), (1, 1), (20, 12), (33, 3), (16, 16)];
+        for &padding in &[0, 1, 2] {
+            let (width, height, origins) = pack_shelves(&sizes, 64, padding);
+            assert_eq!(origins.len(), sizes.len());
+
+            let rects: Vec<_> = sizes.iter().zip(&origins).map(|(&(w, h), &(x, y))| (x, y, w, h)).collect();
+            for (i, &(x, y, w, h)) in rects.iter().enumerate() {
+                assert!(x + w <= width && y + h <= height, "{:?} outside {}x{}", (x, y, w, h), width, height);
+                for &(ox, oy, ow, oh) in &rects[i + 1..] {
+                    let apart = x + w + padding <= ox || ox + ow + padding <= x || y + h + padding <= oy || oy + oh + padding <= y;
+                    assert!(apart, "{:?} overlaps {:?}", (x, y, w, h), (ox, oy, ow, oh));
+                }
+            }
+        }
+
+        // An image wider than the limit widens the atlas rather than overflowing it.
+        let (width, _, origins) = pack_shelves(&[(100, 4), (8, 8)], 64, 0);
+        assert_eq!(width, 100);
+        assert_eq!(origins[0], (0, 8));
+    }
+
+    #[test]
+    fn premultiply_scales_by_alpha() {
+        let mut pixels = [255, 255, 255, 128, 10, 20, 30, 255, 200, 100, 50, 0];
+        premultiply(&mut pixels);
+        assert_eq!(pixels, [128, 128, 128, 128, 10, 20, 30, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn render_region_clips_commands() {
+        let region = Viewport { x: 50, y: 40, width: 100, height: 60 };
+
+        // Commands entirely outside the region, or only touching its edge, are skipped.
+        assert_eq!(intersect_rect((0, 0, 20, 20), region), None);
+        assert_eq!(intersect_rect((200, 120, 30, 30), region), None);
+        assert_eq!(intersect_rect((0, 40, 50, 60), region), None);
+
+        // Partial overlaps are cut down to the region.
+        assert_eq!(intersect_rect((30, 30, 40, 40), region), Some((50, 40, 20, 30)));
+        assert_eq!(intersect_rect((140, 90, 40, 40), region), Some((140, 90, 10, 10)));
+        assert_eq!(intersect_rect((60, 50, 10, 10), region), Some((60, 50, 10, 10)));
+        assert_eq!(intersect_rect((0, 0, 400, 300), region), Some((50, 40, 100, 60)));
+    }
+
+    #[test]
+    fn calls_merge_only_when_contiguous() {
+        let call = |texture, elements: Range<u32>| DrawCall {
+            texture,
+            scissor: (0, 0, 10, 10),
+            locals: 0,
+            base_vertex: 0,
+            elements,
+            pick_id: 0,
+        };
+
+        let mut calls = Vec::new();
+        push_call(&mut calls, call(1, 0..6), true);
+        push_call(&mut calls, call(1, 6..12), true);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].elements, 0..12);
+
+        // A and C match, but B between them was skipped, e.g. for lying outside the render region.
+        push_call(&mut calls, call(1, 18..24), true);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].elements, 0..12);
+        assert_eq!(calls[1].elements, 18..24);
+
+        push_call(&mut calls, call(2, 24..30), true);
+        push_call(&mut calls, call(2, 30..36), false);
+        assert_eq!(calls.len(), 4);
+    }
+
+    #[test]
+    fn wireframe_outlines_triangles() {
+        assert_eq!(triangle_edges(&[0, 1, 2, 0, 2, 3]), [0, 1, 1, 2, 2, 0, 0, 2, 2, 3, 3, 0]);
+        assert!(triangle_edges(&[]).is_empty());
+    }
+
+    #[test]
+    fn mip_chain_length() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(5, 3), 3);
+        assert_eq!(mip_level_count(3, 5), 3);
+        assert_eq!(mip_level_count(100, 60), 7);
+        assert_eq!(mip_level_count(256, 1), 9);
+    }
+
+    #[test]
+    fn downsample_averages_quads() {
+        assert_eq!(downsample(&[10, 20, 30, 41], 2, 2, 1), [25]);
+
+        let rgba = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 0];
+        assert_eq!(downsample(&rgba, 2, 2, 4), [128, 128, 128, 191]);
+
+        // Odd edges reuse the last row or column instead of reading past it.
+        assert_eq!(downsample(&[0, 100, 200, 40, 60, 80], 3, 2, 1), [50]);
+        assert_eq!(downsample(&[8, 16, 24], 1, 3, 1), [12]);
+    }
+
+    #[test]
+    fn upload_unaligned_width() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut alloc = Allocator::new_vec();
+        let mut drawer = DrawerBuilder::new().build(&device, &queue, NkBuffer::new(&mut alloc));
+        let handle = drawer.add_texture(&device, &queue, &[255; 100 * 3 * 4], 100, 3);
+        assert!(drawer.update_texture_region(&device, &queue, handle, 1, 1, 99, 2, &[0; 99 * 2 * 4]).is_ok());
+        device.poll(Maintain::Wait);
+
+        let region = |drawer: &mut Drawer, handle, x, y, w, h| drawer.update_texture_region(&device, &queue, handle, x, y, w, h, &vec![0; (w * h * 4) as usize]);
+        assert_eq!(region(&mut drawer, handle, 99, 2, 1, 1), Ok(()));
+        assert_eq!(region(&mut drawer, handle, 99, 2, 2, 1), Err(TextureError::OutOfBounds));
+        assert_eq!(region(&mut drawer, handle, 0, 3, 1, 1), Err(TextureError::OutOfBounds));
+        assert_eq!(region(&mut drawer, handle, u32::MAX, 0, 2, 1), Err(TextureError::OutOfBounds));
+        // The built-in white texture is not the caller's to write.
+        assert_eq!(region(&mut drawer, Handle::from_id(0), 0, 0, 1, 1), Err(TextureError::MissingTexture(0)));
+        assert_eq!(region(&mut drawer, Handle::from_id(-3), 0, 0, 1, 1), Err(TextureError::MissingTexture(-3)));
+    }
+
+    #[test]
+    fn draw_and_read_back() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut alloc = Allocator::new_vec();
+        let font = null_font();
+        let mut ctx = Context::new(&mut alloc, &font);
+        let mut cfg = ConvertConfig::default();
+        cfg.set_global_alpha(1.0);
+
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::with_size(&mut alloc, 64 * 1024));
+        let mut handle = drawer.add_texture(&device, &queue, &[255; 4 * 4 * 4], 4, 4);
+        assert_eq!(handle.id(), Some(1));
+        assert_eq!(drawer.texture_count(), 1);
+
+        // A borderless window without title or scrollbar draws just its background, here across the top third so a
+        // flipped image would show.
+        ctx.begin(nk_string!("test"), Rect { x: 0.0, y: 0.0, w: 48.0, h: 16.0 }, PanelFlags::NoScrollbar as Flags);
+        ctx.end();
+
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 48, 48, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 48 + x) * 4..][..4];
+        assert_eq!(pixels.len(), 48 * 48 * 4);
+        assert_eq!(pixel(24, 2), [45, 45, 45, 255]);
+        assert_eq!(pixel(2, 13), [45, 45, 45, 255]);
+        assert_eq!(pixel(24, 24), [0, 0, 0, 0]);
+        assert_eq!(pixel(24, 45), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn unflipped_origin_is_bottom_left() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut alloc = Allocator::new_vec();
+        let font = null_font();
+        let mut ctx = Context::new(&mut alloc, &font);
+        let mut cfg = ConvertConfig::default();
+        cfg.set_global_alpha(1.0);
+
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).flip_y(false).build(&device, &queue, NkBuffer::new(&mut alloc));
+        ctx.begin(nk_string!("test"), Rect { x: 0.0, y: 0.0, w: 48.0, h: 16.0 }, PanelFlags::NoScrollbar as Flags);
+        ctx.end();
+
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 48, 48, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 48 + x) * 4..][..4];
+        assert_eq!(pixel(24, 2), [0, 0, 0, 0]);
+        assert_eq!(pixel(24, 24), [0, 0, 0, 0]);
+        assert_eq!(pixel(24, 45), [45, 45, 45, 255]);
+        assert_eq!(pixel(2, 34), [45, 45, 45, 255]);
+    }
+
+    #[test]
+    fn pick_ids_follow_draw_calls() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new()
+            .target_format(TextureFormat::Rgba8Unorm)
+            .picking(TextureFormat::R32Uint)
+            .build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let color = render_target(&device, 48, 48, TextureFormat::Rgba8Unorm);
+        let color_view = color.create_default_view();
+        let pick = render_target(&device, 48, 48, TextureFormat::R32Uint);
+        let pick_view = pick.create_default_view();
+        let one = Vec2 { x: 1.0, y: 1.0 };
+
+        let mut frame = |ctx: &mut Context, drawer: &mut Drawer| {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+            drawer.draw_picking(ctx, &mut cfg, &mut encoder, &color_view, &pick_view, None, &device, 48, 48, one, None, None).unwrap();
+            queue.submit(&[encoder.finish()]);
+            let ids: Vec<u32> = read_texture(&device, &queue, &pick, 48, 48, TextureFormat::R32Uint).chunks(4).map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]])).collect();
+            move |x: usize, y: usize| ids[y * 48 + x]
+        };
+
+        paint(&mut ctx, 48.0, 48.0, |canvas| {
+            canvas.fill_rect(Rect { x: 4.0, y: 4.0, w: 16.0, h: 16.0 }, 0.0, color_rgba(255, 0, 0, 255));
+            canvas.fill_rect(Rect { x: 28.0, y: 28.0, w: 16.0, h: 16.0 }, 0.0, color_rgba(0, 255, 0, 255));
+        });
+        let id = frame(&mut ctx, &mut drawer);
+        assert_eq!(drawer.last_stats().draw_calls, 2);
+        assert_eq!(id(10, 10), 1);
+        assert_eq!(id(36, 36), 2);
+        assert_eq!(id(24, 2), 0);
+
+        // An empty frame that loads the color target still clears the ids.
+        paint(&mut ctx, 48.0, 48.0, |_| {});
+        let id = frame(&mut ctx, &mut drawer);
+        assert_eq!(drawer.last_stats().draw_calls, 0);
+        assert_eq!(id(10, 10), 0);
+        assert_eq!(id(36, 36), 0);
+    }
+
+    #[test]
+    fn wireframe_draws_lines() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).wireframe(true).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let one = Vec2 { x: 1.0, y: 1.0 };
+        let mut capture = |drawer: &mut Drawer| {
+            paint(&mut ctx, 48.0, 48.0, |canvas| canvas.fill_rect(Rect { x: 8.0, y: 8.0, w: 32.0, h: 32.0 }, 0.0, color_rgba(255, 255, 255, 255)));
+            let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 48, 48, one).unwrap();
+            move |x: usize, y: usize| pixels[(y * 48 + x) * 4 + 3]
+        };
+
+        // The rect is two triangles split along its top-left to bottom-right diagonal. Lines on pixel edges may land on
+        // either neighbouring pixel, so both are checked.
+        let alpha = capture(&mut drawer);
+        for &(x, y, dx, dy) in &[(24, 8, 0, 1), (40, 24, 1, 0), (24, 40, 0, 1), (8, 24, 1, 0), (24, 24, 1, 1)] {
+            assert!(alpha(x, y) == 255 || alpha(x - dx, y - dy) == 255, "no edge at {},{}", x, y);
+        }
+        assert_eq!(alpha(30, 16), 0);
+        assert_eq!(alpha(16, 30), 0);
+
+        drawer.set_wireframe(false);
+        let alpha = capture(&mut drawer);
+        assert_eq!(alpha(30, 16), 255);
+        assert_eq!(alpha(16, 30), 255);
+    }
+
+    #[test]
+    fn vertex_colors_render_exactly() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        // Distinct channels, so swapped red and blue or a lost channel would show.
+        for &format in &[TextureFormat::Rgba8Unorm, TextureFormat::Bgra8Unorm] {
+            let mut drawer = DrawerBuilder::new().target_format(format).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+            paint(&mut ctx, 16.0, 16.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, 0.0, color_rgba(200, 40, 10, 255)));
+            let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+            assert_eq!(&pixels[(8 * 16 + 8) * 4..][..4], [200, 40, 10, 255], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn non_square_upload_keeps_rows() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+
+        // 2×4 opaque texels, all different. Drawn 1:1, every pixel center samples a texel center exactly.
+        let image: Vec<u8> = (0..8u8).flat_map(|i| vec![i * 30, 255 - i * 30, i, 255]).collect();
+        let config = TextureConfig {
+            sampler: SamplerConfig::nearest(),
+            ..Default::default()
+        };
+        let mut handle = drawer.add_texture_with_format(&device, &queue, &image, 2, 4, TextureFormat::Rgba8Unorm, config).unwrap();
+        let image_id = handle.id().unwrap();
+
+        paint(&mut ctx, 8.0, 8.0, |canvas| canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 2.0, h: 4.0 }, &Image::with_id(image_id), color_rgba(255, 255, 255, 255)));
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 8, 8, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        for y in 0..4 {
+            assert_eq!(&pixels[y * 8 * 4..][..8], &image[y * 8..][..8], "row {}", y);
+        }
+        assert_eq!(&pixels[4 * 8 * 4..][..8], [0; 8]);
+    }
+
+    #[test]
+    fn resize_updates_projection() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let target = render_target(&device, 32, 32, TextureFormat::Rgba8Unorm);
+        let view = target.create_default_view();
+
+        // Prepared for 48×48, then drawn into a 32×32 target after a resize.
+        paint(&mut ctx, 48.0, 48.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, 0.0, color_rgba(255, 255, 255, 255)));
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        drawer.prepare(&mut ctx, &mut cfg, &mut encoder, &device, 48, 48, Vec2 { x: 1.0, y: 1.0 }, None).unwrap();
+        drawer.resize(&device, &mut encoder, 32, 32);
+        assert_eq!(drawer.target_size(), (32, 32));
+        {
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[RenderPassColorAttachmentDescriptor {
+                    attachment: &view,
+                    resolve_target: None,
+                    load_op: LoadOp::Clear,
+                    store_op: StoreOp::Store,
+                    clear_color: Color::TRANSPARENT,
+                }],
+                depth_stencil_attachment: None,
+            });
+            drawer.draw_into(&mut rpass);
+        }
+        queue.submit(&[encoder.finish()]);
+
+        // The stale 48-pixel projection would have shrunk the rect to about 11 pixels.
+        let pixels = read_texture(&device, &queue, &target, 32, 32, TextureFormat::Rgba8Unorm);
+        let pixel = |x: usize, y: usize| &pixels[(y * 32 + x) * 4..][..4];
+        assert_eq!(pixel(14, 14), [255, 255, 255, 255]);
+        assert_eq!(pixel(17, 17), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn buffers_grow_to_fit() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        // Room for a single triangle.
+        let mut drawer = DrawerBuilder::new()
+            .target_format(TextureFormat::Rgba8Unorm)
+            .vertex_buffer_size(3 * size_of::<Vertex>())
+            .index_buffer_size(8)
+            .build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+
+        // Ten rects of four vertices and six indices each, the last at the bottom-right.
+        paint(&mut ctx, 40.0, 40.0, |canvas| {
+            for i in 0..10 {
+                canvas.fill_rect(
+                    Rect {
+                        x: i as f32 * 4.0,
+                        y: i as f32 * 4.0,
+                        w: 4.0,
+                        h: 4.0,
+                    },
+                    0.0,
+                    color_rgba(255, 255, 255, 255),
+                );
+            }
+        });
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 40, 40, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+
+        assert!(drawer.vertex_buffer_size() >= 40 * size_of::<Vertex>());
+        assert!(drawer.index_buffer_size() >= 60 * size_of::<u16>());
+        assert!(drawer.last_convert_status().vertex_overflow);
+        assert!(drawer.last_convert_status().element_overflow);
+        assert_eq!(drawer.last_stats().vertices, 40);
+        assert_eq!(drawer.last_stats().indices, 60);
+        assert_eq!(&pixels[(38 * 40 + 38) * 4..][..4], [255, 255, 255, 255]);
+
+        // The grown buffers fit the next frame as it is.
+        paint(&mut ctx, 40.0, 40.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 4.0, h: 4.0 }, 0.0, color_rgba(255, 255, 255, 255)));
+        drawer.capture(&mut ctx, &mut cfg, &device, &queue, 40, 40, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        assert_eq!(drawer.last_convert_status(), ConvertStatus::default());
+    }
+
+    #[test]
+    fn unregistered_texture_is_an_error() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        drawer.add_texture(&device, &queue, &[255; 4], 1, 1);
+
+        paint(&mut ctx, 16.0, 16.0, |canvas| {
+            canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 255, 255, 255));
+            canvas.draw_image(Rect { x: 8.0, y: 8.0, w: 8.0, h: 8.0 }, &Image::with_id(7), color_rgba(255, 255, 255, 255));
+        });
+        let result = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 });
+        assert_eq!(result, Err(DrawError::MissingTexture(7)));
+        assert_eq!(drawer.last_stats().draw_calls, 0);
+    }
+
+    #[test]
+    fn texture_lookup_failures() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        assert!(drawer.find_res(0).is_some());
+        assert!(drawer.find_res(1).is_none());
+        assert!(drawer.find_res(-1).is_none());
+        assert!(drawer.find_res(i32::MIN).is_none());
+
+        let handle = drawer.add_texture(&device, &queue, &[255; 4], 1, 1);
+        assert!(drawer.find_res(1).is_some());
+        assert!(drawer.find_res(2).is_none());
+
+        // Handles that were never given out, or are already released, are ignored rather than panicking.
+        for &id in &[0, -1, 2, i32::MAX] {
+            drawer.remove_texture(Handle::from_id(id));
+        }
+        assert_eq!(drawer.texture_count(), 1);
+        drawer.remove_texture(handle);
+        drawer.remove_texture(handle);
+        assert!(drawer.find_res(1).is_none());
+        assert!(drawer.find_res(0).is_some());
+        assert_eq!(drawer.update_texture_region(&device, &queue, handle, 0, 0, 1, 1, &[0; 4]), Err(TextureError::MissingTexture(1)));
+    }
+
+    #[test]
+    fn pointer_handles_dont_panic() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let one = Vec2 { x: 1.0, y: 1.0 };
+
+        // Command handles carry no kind, so a null pointer reads as id 0 and draws untextured.
+        let null = unsafe { Image::with_ptr(std::ptr::null_mut()) };
+        paint(&mut ctx, 16.0, 16.0, |canvas| canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, &null, color_rgba(255, 255, 255, 255)));
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, one).unwrap();
+        assert_eq!(&pixels[(8 * 16 + 8) * 4..][..4], [255, 255, 255, 255]);
+
+        // Any other pointer matches no slot and fails the frame like an unknown id.
+        let dangling = unsafe { Image::with_ptr(0x40 as *mut _) };
+        paint(&mut ctx, 16.0, 16.0, |canvas| canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, &dangling, color_rgba(255, 255, 255, 255)));
+        assert_eq!(drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, one), Err(DrawError::MissingTexture(0x40)));
+    }
+
+    #[test]
+    fn validation_reports_missing_textures() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        warnings();
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let one = Vec2 { x: 1.0, y: 1.0 };
+        // Ids no other test uses, as every test logs to the same place.
+        let mentions = |id: i32| warnings().iter().any(|w| w.contains(&format!("unregistered texture id {}", id)));
+
+        paint(&mut ctx, 16.0, 16.0, |canvas| canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, &Image::with_id(9901), color_rgba(255, 255, 255, 255)));
+        assert!(drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, one).is_err());
+        assert!(!mentions(9901));
+
+        drawer.set_validation(true);
+        paint(&mut ctx, 16.0, 16.0, |canvas| canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, &Image::with_id(9902), color_rgba(255, 255, 255, 255)));
+        assert_eq!(drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, one), Err(DrawError::MissingTexture(9902)));
+        assert!(mentions(9902));
+    }
+
+    #[test]
+    fn merging_reduces_draw_calls() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let white = drawer.add_texture(&device, &queue, &[255; 4], 1, 1).id().unwrap();
+        let one = Vec2 { x: 1.0, y: 1.0 };
+
+        // Five commands with the same texture and clip rect.
+        paint(&mut ctx, 48.0, 48.0, |canvas| {
+            for i in 0..5 {
+                canvas.fill_rect(Rect { x: i as f32 * 8.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 0, 0, 255));
+            }
+        });
+        drawer.capture(&mut ctx, &mut cfg, &device, &queue, 48, 48, one).unwrap();
+        assert_eq!(drawer.last_stats().draw_calls, 1);
+        assert_eq!(drawer.last_stats().indices, 5 * 6);
+
+        // A texture change in the middle splits the run.
+        paint(&mut ctx, 48.0, 48.0, |canvas| {
+            canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 0, 0, 255));
+            canvas.draw_image(Rect { x: 8.0, y: 0.0, w: 8.0, h: 8.0 }, &Image::with_id(white), color_rgba(255, 255, 255, 255));
+            canvas.fill_rect(Rect { x: 16.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 0, 0, 255));
+            canvas.fill_rect(Rect { x: 24.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 0, 0, 255));
+        });
+        drawer.capture(&mut ctx, &mut cfg, &device, &queue, 48, 48, one).unwrap();
+        assert_eq!(drawer.last_stats().draw_calls, 3);
+    }
+
+    #[test]
+    fn stats_count_triangles() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+
+        // Three rects of two triangles each and a single triangle.
+        paint(&mut ctx, 48.0, 48.0, |canvas| {
+            for i in 0..3 {
+                canvas.fill_rect(Rect { x: i as f32 * 8.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 255, 255, 255));
+            }
+            canvas.fill_triangle(0.0, 16.0, 16.0, 16.0, 0.0, 32.0, color_rgba(255, 255, 255, 255));
+        });
+        drawer.capture(&mut ctx, &mut cfg, &device, &queue, 48, 48, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+
+        let stats = drawer.last_stats();
+        assert_eq!(stats.vertices, 3 * 4 + 3);
+        assert_eq!(stats.indices, 3 * 6 + 3);
+        assert_eq!(stats.triangles, stats.indices / 3);
+        assert_eq!(stats.triangles, 7);
+    }
+
+    #[test]
+    fn uis_share_one_index_buffer() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut first, mut cfg) = context(&font);
+        let (mut second, _) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let target = render_target(&device, 32, 32, TextureFormat::Rgba8Unorm);
+        let view = target.create_default_view();
+        let full = Viewport { x: 0, y: 0, width: 32, height: 32 };
+
+        // Both UIs index their vertices from 0, so the second only lands right if it is drawn from its own region.
+        paint(&mut first, 32.0, 32.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 0, 0, 255)));
+        paint(&mut second, 32.0, 32.0, |canvas| canvas.fill_rect(Rect { x: 16.0, y: 16.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(0, 255, 0, 255)));
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let mut uis = [(&mut first, full), (&mut second, full)];
+        drawer.draw_multi(&mut uis, &mut cfg, &mut encoder, &view, None, None, &device, 32, 32, Vec2 { x: 1.0, y: 1.0 }, Some(Color::TRANSPARENT)).unwrap();
+        queue.submit(&[encoder.finish()]);
+
+        assert_eq!(drawer.calls.len(), 2);
+        assert_eq!((drawer.calls[0].base_vertex, drawer.calls[0].elements.clone()), (0, 0..6));
+        assert_eq!((drawer.calls[1].base_vertex, drawer.calls[1].elements.clone()), (4, 6..12));
+
+        let pixels = read_texture(&device, &queue, &target, 32, 32, TextureFormat::Rgba8Unorm);
+        let pixel = |x: usize, y: usize| &pixels[(y * 32 + x) * 4..][..4];
+        assert_eq!(pixel(4, 4), [255, 0, 0, 255]);
+        assert_eq!(pixel(20, 20), [0, 255, 0, 255]);
+        assert_eq!(pixel(12, 12), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn viewports_get_their_own_projection() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut left, mut cfg) = context(&font);
+        let (mut right, _) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let target = render_target(&device, 64, 32, TextureFormat::Rgba8Unorm);
+        let view = target.create_default_view();
+
+        // The same logical rect in each half of a split screen.
+        paint(&mut left, 32.0, 32.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 0, 0, 255)));
+        paint(&mut right, 32.0, 32.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(0, 255, 0, 255)));
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let mut uis = [(&mut left, Viewport { x: 0, y: 0, width: 32, height: 32 }), (&mut right, Viewport { x: 32, y: 0, width: 32, height: 32 })];
+        drawer.draw_multi(&mut uis, &mut cfg, &mut encoder, &view, None, None, &device, 64, 32, Vec2 { x: 1.0, y: 1.0 }, Some(Color::TRANSPARENT)).unwrap();
+        queue.submit(&[encoder.finish()]);
+
+        let pixels = read_texture(&device, &queue, &target, 64, 32, TextureFormat::Rgba8Unorm);
+        let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..][..4];
+        assert_eq!(pixel(4, 4), [255, 0, 0, 255]);
+        assert_eq!(pixel(36, 4), [0, 255, 0, 255]);
+        assert_eq!(pixel(20, 4), [0, 0, 0, 0]);
+        assert_eq!(pixel(52, 4), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn hidden_ui_leaves_target_alone() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let target = render_target(&device, 16, 16, TextureFormat::Rgba8Unorm);
+        let view = target.create_default_view();
+
+        // A scene the UI is composited over.
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &[RenderPassColorAttachmentDescriptor {
+                attachment: &view,
+                resolve_target: None,
+                load_op: LoadOp::Clear,
+                store_op: StoreOp::Store,
+                clear_color: Color { r: 0.2, g: 0.4, b: 0.6, a: 1.0 },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        // A window without anything in it converts to no commands at all.
+        paint(&mut ctx, 16.0, 16.0, |_| {});
+        assert_eq!(drawer.load_behavior(), LoadBehavior::Load);
+        drawer.draw(&mut ctx, &mut cfg, &mut encoder, &view, None, None, &device, 16, 16, Vec2 { x: 1.0, y: 1.0 }, None, None).unwrap();
+        queue.submit(&[encoder.finish()]);
+        assert!(drawer.calls.is_empty());
+        assert_eq!(drawer.last_stats(), DrawStats::default());
+
+        let pixels = read_texture(&device, &queue, &target, 16, 16, TextureFormat::Rgba8Unorm);
+        assert!(pixels.chunks(4).all(|px| px == [51, 102, 153, 255]));
+    }
+
+    #[test]
+    fn empty_frame_still_clears() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        drawer.set_clear_color(Some(Color { r: 0.2, g: 0.4, b: 0.6, a: 1.0 }));
+
+        // Nothing converted, so nothing may be bound: wgpu's validation would fail the capture otherwise.
+        paint(&mut ctx, 16.0, 16.0, |_| {});
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        assert_eq!(drawer.last_stats(), DrawStats::default());
+        assert!(pixels.chunks(4).all(|px| px == [51, 102, 153, 255]));
+    }
+
+    #[test]
+    fn zero_size_draws_nothing() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let target = render_target(&device, 16, 16, TextureFormat::Rgba8Unorm);
+        let view = target.create_default_view();
+
+        // A minimized window reports a zero size for one or both sides.
+        for &(width, height) in &[(16, 0), (0, 16), (0, 0)] {
+            paint(&mut ctx, 16.0, 16.0, |canvas| canvas.fill_rect(Rect { x: 0.0, y: 0.0, w: 8.0, h: 8.0 }, 0.0, color_rgba(255, 255, 255, 255)));
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+            drawer.draw(&mut ctx, &mut cfg, &mut encoder, &view, None, None, &device, width, height, Vec2 { x: 1.0, y: 1.0 }, None, None).unwrap();
+            queue.submit(&[encoder.finish()]);
+            assert!(drawer.calls.is_empty());
+            assert_eq!(drawer.last_stats().draw_calls, 0);
+        }
+    }
+
+    #[test]
+    fn removal_keeps_other_handles() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let mut ids = Vec::new();
+        for color in &[[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]] {
+            ids.push(drawer.add_texture_rgba(&device, &queue, color, 1, 1).id().unwrap());
+        }
+        drawer.remove_texture(Handle::from_id(ids[1]));
+
+        assert!(drawer.find_res(ids[0]).is_some());
+        assert!(drawer.find_res(ids[1]).is_none());
+        assert!(drawer.find_res(ids[2]).is_some());
+
+        // The survivors still draw their own images.
+        paint(&mut ctx, 16.0, 16.0, |canvas| {
+            canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 8.0, h: 8.0 }, &Image::with_id(ids[0]), color_rgba(255, 255, 255, 255));
+            canvas.draw_image(Rect { x: 8.0, y: 8.0, w: 8.0, h: 8.0 }, &Image::with_id(ids[2]), color_rgba(255, 255, 255, 255));
+        });
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        assert_eq!(&pixels[(4 * 16 + 4) * 4..][..4], [255, 0, 0, 255]);
+        assert_eq!(&pixels[(12 * 16 + 12) * 4..][..4], [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn texture_count_and_capacity() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().texture_capacity(4).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        assert_eq!(drawer.texture_count(), 0);
+        assert!(drawer.texture_capacity() >= 4);
+
+        let handles: Vec<_> = (0..3).map(|_| drawer.add_texture(&device, &queue, &[255; 4], 1, 1)).collect();
+        assert_eq!(drawer.texture_count(), 3);
+        assert!(drawer.texture_capacity() >= 3);
+
+        // Removed slots stay allocated but no longer count.
+        drawer.remove_texture(handles[0]);
+        assert_eq!(drawer.texture_count(), 2);
+        assert!(drawer.texture_capacity() >= 3);
+    }
+
+    #[test]
+    fn handles_survive_batch_add() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().texture_capacity(1).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let mut first = drawer.add_texture(&device, &queue, &[255; 4], 1, 1);
+        drawer.reserve_textures(100);
+        assert!(drawer.texture_capacity() >= 101);
+
+        // Past the reservation too, so the list reallocates at least once.
+        let ids: Vec<i32> = (0..200).map(|i| drawer.add_texture(&device, &queue, &[i as u8; 4], 1, 1).id().unwrap()).collect();
+        assert_eq!(first.id(), Some(1));
+        assert_eq!(ids, (2..202).collect::<Vec<_>>());
+        assert!(ids.iter().chain(Some(&1)).all(|&id| drawer.find_res(id).is_some()));
+        assert_eq!(drawer.texture_count(), 201);
+    }
+
+    #[test]
+    fn ids_restart_after_clear() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        for _ in 0..3 {
+            drawer.add_texture(&device, &queue, &[255; 4], 1, 1);
+        }
+        drawer.clear_textures();
+        assert_eq!(drawer.texture_count(), 0);
+        assert!(drawer.find_res(1).is_none());
+        // The built-in white texture isn't the caller's and survives.
+        assert!(drawer.find_res(0).is_some());
+
+        assert_eq!(drawer.add_texture(&device, &queue, &[255; 4], 1, 1).id(), Some(1));
+        assert_eq!(drawer.texture_count(), 1);
+    }
+
+    #[test]
+    fn handles_enumerate_live_slots() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let added: Vec<_> = (0..3).map(|_| drawer.add_texture(&device, &queue, &[255; 4], 1, 1)).collect();
+        let ids = |drawer: &Drawer| drawer.texture_handles().map(|mut handle| handle.id().unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids(&drawer), [1, 2, 3]);
+        assert!(ids(&drawer).iter().all(|&id| drawer.find_res(id).is_some()));
+
+        drawer.remove_texture(added[1]);
+        assert_eq!(ids(&drawer), [1, 3]);
+    }
+
+    #[test]
+    fn srgb_target_keeps_mid_gray() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        // Vertex colors are sRGB, so a mid-gray converted to linear and encoded again by the target reads back unchanged.
+        // Written without conversion it would come out around 188.
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).srgb(true).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        assert_eq!(drawer.target_format(), TextureFormat::Rgba8UnormSrgb);
+        let px = capture_fill(&mut drawer, &device, &queue, color_rgba(128, 128, 128, 255));
+        for &c in &px[..3] {
+            assert!((126..=130).contains(&c), "{:?}", px);
+        }
+        assert_eq!(px[3], 255);
+    }
+
+    #[test]
+    fn srgb_clear_color_is_encoded() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).srgb(true).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        drawer.set_clear_color(Some(Color { r: 0.5, g: 0.5, b: 0.5, a: 0.5 }));
+
+        // 0.5 in sRGB encodes to 127.5 either way; passed through as linear it would read back as 188. Alpha is linear.
+        paint(&mut ctx, 16.0, 16.0, |_| {});
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        for px in pixels.chunks(4) {
+            assert!(px.iter().all(|&c| c == 127 || c == 128), "{:?}", px);
+        }
+    }
+
+    #[test]
+    fn translucent_over_transparent_keeps_alpha() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        // Over a transparent target the destination alpha is the panel's own, not its square.
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let px = capture_fill(&mut drawer, &device, &queue, color_rgba(255, 255, 255, 128));
+        assert!((127..=129).contains(&px[3]), "{:?}", px);
+        assert!((127..=129).contains(&px[0]), "{:?}", px);
+
+        // Premultiplied colors land the same.
+        let mut drawer = DrawerBuilder::new()
+            .target_format(TextureFormat::Rgba8Unorm)
+            .blend_mode(BlendMode::PremultipliedAlpha)
+            .build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let px = capture_fill(&mut drawer, &device, &queue, color_rgba(128, 128, 128, 128));
+        assert!((127..=129).contains(&px[3]), "{:?}", px);
+        assert!((127..=129).contains(&px[0]), "{:?}", px);
+    }
+
+    #[test]
+    fn tint_alpha_halves_output() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        assert_eq!(capture_fill(&mut drawer, &device, &queue, color_rgba(255, 255, 255, 255)), [255, 255, 255, 255]);
+
+        drawer.set_tint([1.0, 1.0, 1.0, 0.5]);
+        let px = capture_fill(&mut drawer, &device, &queue, color_rgba(255, 255, 255, 255));
+        assert!((127..=128).contains(&px[3]), "{:?}", px);
+    }
+
+    #[test]
+    fn gamma_brightens_above_one() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let gray = color_rgba(128, 128, 128, 255);
+        assert_eq!(capture_fill(&mut drawer, &device, &queue, gray), [128, 128, 128, 255]);
+
+        // 0.5 ^ (1 / 2) and 0.5 ^ 2; alpha is left alone.
+        drawer.set_gamma(2.0);
+        let px = capture_fill(&mut drawer, &device, &queue, gray);
+        assert!((178..=183).contains(&px[0]) && px[3] == 255, "{:?}", px);
+        drawer.set_gamma(0.5);
+        let px = capture_fill(&mut drawer, &device, &queue, gray);
+        assert!((62..=66).contains(&px[0]) && px[3] == 255, "{:?}", px);
+
+        drawer.set_gamma(100.0);
+        assert_eq!(drawer.gamma(), 5.0);
+        drawer.set_gamma(f32::NAN);
+        assert_eq!(drawer.gamma(), 1.0);
+    }
+
+    #[test]
+    fn hdr_target_renders_linear_color() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        // Normal half floats only, which is all a UI produces.
+        fn half(bits: u16) -> f32 {
+            let exponent = ((bits >> 10) & 0x1f) as i32;
+            let value = if exponent == 0 { 0.0 } else { (1.0 + (bits & 0x3ff) as f32 / 1024.0) * 2f32.powi(exponent - 15) };
+            if bits & 0x8000 != 0 {
+                -value
+            } else {
+                value
+            }
+        }
+
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba16Float).srgb(true).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        assert_eq!(drawer.target_format(), TextureFormat::Rgba16Float);
+        let channels = |px: Vec<u8>| px.chunks(2).map(|b| half(u16::from_le_bytes([b[0], b[1]]))).collect::<Vec<_>>();
+
+        assert_eq!(channels(capture_fill(&mut drawer, &device, &queue, color_rgba(255, 0, 0, 255))), [1.0, 0.0, 0.0, 1.0]);
+
+        // sRGB mid-gray is about 0.216 in linear light.
+        let gray = channels(capture_fill(&mut drawer, &device, &queue, color_rgba(128, 128, 128, 255)));
+        assert!(gray[..3].iter().all(|&c| (c - 0.216).abs() < 0.005), "{:?}", gray);
+        assert_eq!(gray[3], 1.0);
+    }
+
+    #[test]
+    fn red_rgba_renders_red() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        for &format in &[TextureFormat::Rgba8Unorm, TextureFormat::Bgra8Unorm] {
+            let mut drawer = DrawerBuilder::new().target_format(format).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+            // The same red given in each input order.
+            let rgba = drawer.add_texture_rgba(&device, &queue, &[255, 0, 0, 255], 1, 1).id().unwrap();
+            let bgra = drawer.add_texture(&device, &queue, &[0, 0, 255, 255], 1, 1).id().unwrap();
+
+            paint(&mut ctx, 16.0, 16.0, |canvas| {
+                canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 8.0, h: 16.0 }, &Image::with_id(rgba), color_rgba(255, 255, 255, 255));
+                canvas.draw_image(Rect { x: 8.0, y: 0.0, w: 8.0, h: 16.0 }, &Image::with_id(bgra), color_rgba(255, 255, 255, 255));
+            });
+            let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+            assert_eq!(&pixels[(8 * 16 + 4) * 4..][..4], [255, 0, 0, 255], "{:?}", format);
+            assert_eq!(&pixels[(8 * 16 + 12) * 4..][..4], [255, 0, 0, 255], "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn equal_sampler_configs_share() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        // The built-in white texture already uses the default sampler.
+        let mut drawer = DrawerBuilder::new().build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        assert_eq!(drawer.sampler_count(), 1);
+        drawer.add_texture(&device, &queue, &[255; 4], 1, 1);
+        drawer.add_texture_with_sampler(&device, &queue, &[255; 4], 1, 1, SamplerConfig::default());
+        assert_eq!(drawer.sampler_count(), 1);
+
+        drawer.add_texture_with_sampler(&device, &queue, &[255; 4], 1, 1, SamplerConfig::nearest());
+        drawer.add_texture_with_sampler(&device, &queue, &[255; 4], 1, 1, SamplerConfig::nearest());
+        assert_eq!(drawer.sampler_count(), 2);
+    }
+
+    #[test]
+    fn filter_modes_apply() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let black_white = [0, 0, 0, 255, 255, 255, 255, 255];
+        let linear = drawer.add_texture_with_format(&device, &queue, &black_white, 2, 1, TextureFormat::Rgba8Unorm, TextureConfig::default()).unwrap().id().unwrap();
+        let config = TextureConfig {
+            sampler: SamplerConfig::nearest(),
+            ..Default::default()
+        };
+        let nearest = drawer.add_texture_with_format(&device, &queue, &black_white, 2, 1, TextureFormat::Rgba8Unorm, config).unwrap().id().unwrap();
+        assert_eq!(drawer.sampler_count(), 2);
+        assert_eq!(drawer.find_res(nearest).unwrap().sampler.mag_filter, FilterMode::Nearest);
+
+        // Stretched over 16 pixels, the pixel just left of the middle is still in the black texel but close to the
+        // white one.
+        paint(&mut ctx, 16.0, 16.0, |canvas| {
+            canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 16.0, h: 8.0 }, &Image::with_id(linear), color_rgba(255, 255, 255, 255));
+            canvas.draw_image(Rect { x: 0.0, y: 8.0, w: 16.0, h: 8.0 }, &Image::with_id(nearest), color_rgba(255, 255, 255, 255));
+        });
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        let red = |x: usize, y: usize| pixels[(y * 16 + x) * 4];
+        assert!((100..=124).contains(&red(7, 4)), "{}", red(7, 4));
+        assert_eq!(red(7, 12), 0);
+        assert_eq!(red(8, 12), 255);
+    }
+
+    #[test]
+    fn repeat_mode_applies() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let id = drawer.add_texture_with_sampler(&device, &queue, &[255; 4], 1, 1, SamplerConfig::repeat()).id().unwrap();
+        let sampler = drawer.find_res(id).unwrap().sampler;
+        assert_eq!((sampler.address_mode_u, sampler.address_mode_v), (AddressMode::Repeat, AddressMode::Repeat));
+        assert!(drawer.smp.iter().any(|(config, _)| *config == SamplerConfig::repeat()));
+    }
+
+    #[test]
+    fn lod_range_applies() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let mut drawer = DrawerBuilder::new().build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+        let config = SamplerConfig {
+            lod_min_clamp: 1.0,
+            lod_max_clamp: 3.0,
+            ..Default::default()
+        };
+        let id = drawer.add_texture_with_sampler(&device, &queue, &[255; 4], 1, 1, config).id().unwrap();
+        let sampler = drawer.find_res(id).unwrap().sampler;
+        assert_eq!((sampler.lod_min_clamp, sampler.lod_max_clamp), (1.0, 3.0));
+        assert_eq!(drawer.sampler_count(), 2);
+        assert!(drawer.smp.iter().any(|(stored, _)| *stored == config));
+    }
+
+    #[test]
+    fn buffers_below_one_triangle_rejected() {
+        let stride = size_of::<Vertex>();
+        assert!(check_buffer_sizes(stride, 3 * stride, 8).is_ok());
+
+        let too_small = |vbo_size, ebo_size, expected: BufferUsage| match check_buffer_sizes(stride, vbo_size, ebo_size) {
+            Err(DrawerError::BufferTooSmall { usage, size, minimum }) => usage == expected && size < minimum,
+            _ => false,
+        };
+        assert!(too_small(0, 8, BufferUsage::VERTEX));
+        assert!(too_small(3 * stride - 1, 8, BufferUsage::VERTEX));
+        assert!(too_small(3 * stride, 0, BufferUsage::INDEX));
+        assert!(too_small(3 * stride, 7, BufferUsage::INDEX));
+    }
+
+    #[cfg(feature = "glsl-runtime")]
+    #[test]
+    fn broken_glsl_is_an_error() {
+        match compile_glsl("#version 450\nvoid main() { this is not glsl }", glsl_to_spirv::ShaderType::Fragment) {
+            Err(DrawerError::ShaderCompile(diagnostics)) => assert!(!diagnostics.is_empty()),
+            other => panic!("expected a compile error, got {:?}", other.map(|spv| spv.len())),
+        }
+    }
+
+    #[test]
+    fn build_and_destroy_repeatedly() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        for _ in 0..16 {
+            let mut drawer = DrawerBuilder::new().target_format(TextureFormat::Rgba8Unorm).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+            let red = drawer.add_texture(&device, &queue, &[255, 0, 0, 255], 1, 1).id().unwrap();
+            drawer.add_texture_with_sampler(&device, &queue, &[255; 4], 1, 1, SamplerConfig::nearest());
+            assert_eq!(drawer.texture_count(), 2);
+
+            paint(&mut ctx, 16.0, 16.0, |canvas| canvas.draw_image(Rect { x: 0.0, y: 0.0, w: 16.0, h: 16.0 }, &Image::with_id(red), color_rgba(255, 255, 255, 255)));
+            let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 16, 16, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+            assert_eq!(pixels[..4], [255, 0, 0, 255]);
+
+            drawer.destroy();
+            device.poll(Maintain::Wait);
+        }
+    }
+
+    #[cfg(not(feature = "glsl-runtime"))]
+    #[test]
+    fn spirv_bytes_round_trip() {
+        let words = [0x0723_0203, 0x0001_0000, 0x0008_0001, 0x0000_000d, 0];
+        let little: Vec<u8> = words.iter().flat_map(|w: &u32| w.to_le_bytes().to_vec()).collect();
+        let big: Vec<u8> = words.iter().flat_map(|w: &u32| w.to_be_bytes().to_vec()).collect();
+        assert_eq!(little[..4], [0x03, 0x02, 0x23, 0x07]);
+        assert_eq!(load_spirv(&little), words);
+        assert_eq!(load_spirv(&big), words);
+
+        let vs = load_spirv(VS_SPIRV);
+        assert_eq!((vs[0], vs.len()), (0x0723_0203, VS_SPIRV.len() / 4));
+    }
+
+    #[test]
+    fn capture_solid_clear() {
+        let (device, queue) = match device() {
+            Some(device) => device,
+            None => return,
+        };
+
+        let font = null_font();
+        let (mut ctx, mut cfg) = context(&font);
+        // The default Bgra8Unorm target, 37 pixels wide so every row is padded up to 256 bytes for the copy.
+        let mut drawer = DrawerBuilder::new().clear_color(Some(Color { r: 0.2, g: 0.4, b: 0.6, a: 1.0 })).build(&device, &queue, NkBuffer::new(&mut Allocator::new_vec()));
+
+        paint(&mut ctx, 37.0, 5.0, |_| {});
+        let pixels = drawer.capture(&mut ctx, &mut cfg, &device, &queue, 37, 5, Vec2 { x: 1.0, y: 1.0 }).unwrap();
+        assert_eq!(pixels.len(), 37 * 5 * 4);
+        assert!(pixels.chunks(4).all(|px| px == [51, 102, 153, 255]));
+    }
 }
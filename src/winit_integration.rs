@@ -0,0 +1,184 @@
+//! Glue between a winit window and a [`Drawer`]: forwards window events to nuklear and owns the swap chain.
+//!
+//! ```no_run
+//! # use nuklear::{Context, ConvertConfig};
+//! # use nuklear_backend_wgpurs::{winit_integration::WinitPlatform, Drawer};
+//! # use wgpu::{Device, Queue, Surface};
+//! # use winit::{event::Event, event_loop::EventLoop, window::Window};
+//! # fn run(event_loop: EventLoop<()>, window: Window, device: Device, queue: Queue, mut drawer: Drawer, mut ctx: Context, mut cfg: ConvertConfig) {
+//! let surface = Surface::create(&window);
+//! let mut platform = WinitPlatform::new(&device, surface, &window, drawer.target_format());
+//!
+//! event_loop.run(move |event, _, _| match event {
+//!     Event::NewEvents(_) => ctx.input_begin(),
+//!     Event::WindowEvent { ref event, .. } => platform.handle_event(&mut ctx, &device, event),
+//!     Event::MainEventsCleared => {
+//!         ctx.input_end();
+//!         // build the UI
+//!         platform.render(&mut drawer, &mut ctx, &mut cfg, &device, &queue).unwrap();
+//!         ctx.clear();
+//!     }
+//!     _ => {}
+//! });
+//! # }
+//! ```
+
+use nuklear::{Button, Context, ConvertConfig, Key, Vec2};
+
+use wgpu::{Device, PresentMode, Queue, Surface, SwapChain, SwapChainDescriptor, TextureFormat, TextureUsage};
+
+use winit::{
+    event::{ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent},
+    window::Window,
+};
+
+use crate::{DrawError, Drawer};
+
+pub struct WinitPlatform {
+    surface: Surface,
+    swap_chain: SwapChain,
+    desc: SwapChainDescriptor,
+    scale: f64,
+    // Last cursor position in nuklear's logical units.
+    cursor: (i32, i32),
+    modifiers: ModifiersState,
+}
+
+impl WinitPlatform {
+    /// `format` must be the drawer's [`Drawer::target_format`].
+    pub fn new(device: &Device, surface: Surface, window: &Window, format: TextureFormat) -> Self {
+        let size = window.inner_size();
+        let desc = SwapChainDescriptor {
+            usage: TextureUsage::OUTPUT_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: PresentMode::Fifo,
+        };
+        WinitPlatform {
+            swap_chain: device.create_swap_chain(&surface, &desc),
+            surface,
+            desc,
+            scale: window.scale_factor(),
+            cursor: (0, 0),
+            modifiers: ModifiersState::default(),
+        }
+    }
+
+    /// Forwards input to nuklear and follows resizes. Call it between `ctx.input_begin()` and `ctx.input_end()`.
+    pub fn handle_event(&mut self, ctx: &mut Context, device: &Device, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Resized(size) => self.resize(device, size.width, size.height),
+            WindowEvent::ScaleFactorChanged { scale_factor, ref new_inner_size } => {
+                self.scale = scale_factor;
+                self.resize(device, new_inner_size.width, new_inner_size.height);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = to_logical(position.x, position.y, self.scale);
+                ctx.input_motion(self.cursor.0, self.cursor.1);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => Button::Left,
+                    MouseButton::Middle => Button::Middle,
+                    MouseButton::Right => Button::Right,
+                    _ => return,
+                };
+                ctx.input_button(button, self.cursor.0, self.cursor.1, state == ElementState::Pressed);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (x, y),
+                    // Roughly one line per 20 logical pixels.
+                    MouseScrollDelta::PixelDelta(p) => ((p.x / 20.0) as f32, (p.y / 20.0) as f32),
+                };
+                ctx.input_scroll(Vec2 { x, y });
+            }
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => ctx.input_unicode(c),
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput { state, virtual_keycode: Some(code), .. },
+                ..
+            } => {
+                if let Some(key) = map_key(code, self.modifiers.ctrl()) {
+                    ctx.input_key(key, state == ElementState::Pressed);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Renders the converted UI into the next swap chain frame and presents it. A frame the swap chain times out on is
+    /// skipped rather than reported as an error.
+    pub fn render(&mut self, drawer: &mut Drawer, ctx: &mut Context, cfg: &mut ConvertConfig, device: &Device, queue: &Queue) -> Result<(), DrawError> {
+        let frame = match self.swap_chain.get_next_texture() {
+            Ok(frame) => frame,
+            Err(_) => {
+                warn!("timed out waiting for a swap chain frame, skipping it");
+                return Ok(());
+            }
+        };
+        let scale = Vec2 { x: self.scale as f32, y: self.scale as f32 };
+        drawer.render_frame(ctx, cfg, device, queue, &frame.view, self.desc.width, self.desc.height, scale)
+    }
+
+    fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        // Minimized windows report a zero size, which the swap chain can't have; keep the old one until restored.
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.desc.width = width;
+        self.desc.height = height;
+        self.swap_chain = device.create_swap_chain(&self.surface, &self.desc);
+    }
+}
+
+// Nuklear works in logical units, while winit reports the cursor in physical pixels.
+fn to_logical(x: f64, y: f64, scale: f64) -> (i32, i32) {
+    ((x / scale) as i32, (y / scale) as i32)
+}
+
+fn map_key(code: VirtualKeyCode, ctrl: bool) -> Option<Key> {
+    Some(match code {
+        VirtualKeyCode::LShift | VirtualKeyCode::RShift => Key::Shift,
+        VirtualKeyCode::LControl | VirtualKeyCode::RControl => Key::Ctrl,
+        VirtualKeyCode::Delete => Key::Del,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Up => Key::Up,
+        VirtualKeyCode::Down => Key::Down,
+        VirtualKeyCode::Left => Key::Left,
+        VirtualKeyCode::Right => Key::Right,
+        VirtualKeyCode::Home => Key::TextStart,
+        VirtualKeyCode::End => Key::TextEnd,
+        VirtualKeyCode::C if ctrl => Key::Copy,
+        VirtualKeyCode::X if ctrl => Key::Cut,
+        VirtualKeyCode::V if ctrl => Key::Paste,
+        VirtualKeyCode::Z if ctrl => Key::TextUndo,
+        VirtualKeyCode::Y if ctrl => Key::TextRedo,
+        VirtualKeyCode::A if ctrl => Key::TextSelectAll,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ctrl_shortcuts() {
+        assert!(map_key(VirtualKeyCode::C, true) == Some(Key::Copy));
+        assert!(map_key(VirtualKeyCode::C, false).is_none());
+        assert!(map_key(VirtualKeyCode::V, true) == Some(Key::Paste));
+        assert!(map_key(VirtualKeyCode::Back, false) == Some(Key::Backspace));
+        assert!(map_key(VirtualKeyCode::Back, true) == Some(Key::Backspace));
+    }
+
+    #[test]
+    fn cursor_in_logical_units() {
+        assert_eq!(to_logical(300.0, 151.0, 1.0), (300, 151));
+        assert_eq!(to_logical(300.0, 151.0, 2.0), (150, 75));
+        assert_eq!(to_logical(300.0, 150.0, 1.5), (200, 100));
+    }
+}